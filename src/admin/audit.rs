@@ -0,0 +1,59 @@
+use std::{fs::OpenOptions, io::Write, path::{Path, PathBuf}, sync::Mutex};
+
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::{date::DateTime, secret};
+
+const AUDIT_FILE: &str = "audit.log";
+
+#[derive(Serialize)]
+struct Record<'a> {
+    timestamp: DateTime,
+    admin: &'a str,
+    action: &'a str,
+    application: &'a str,
+    outcome: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    detail: Option<&'a str>
+}
+
+/// Registro de auditoria das alterações feitas pela API, uma linha JSON por alteração.
+pub(crate) struct Audit {
+    path: PathBuf,
+    file: Mutex<()>
+}
+
+impl Audit {
+    pub(crate) fn new(storage: &Path) -> Self {
+        Audit { path: storage.join(AUDIT_FILE), file: Mutex::new(()) }
+    }
+
+    /// `Err` traz o motivo da recusa; falhas ao gravar o registro não desfazem a alteração, só são logadas.
+    pub(crate) fn record(&self, admin: &str, action: &str, application: &str, outcome: Result<(), &str>) {
+        let record = Record {
+            timestamp: DateTime::now(),
+            admin,
+            action,
+            application,
+            outcome: if outcome.is_ok() { "applied" } else { "rejected" },
+            detail: outcome.err()
+        };
+
+        match outcome {
+            Ok(()) => info!(admin, action, application, "Alteração aplicada pela API de administração"),
+            Err(detail) => warn!(admin, action, application, detail, "Alteração recusada pela API de administração"),
+        }
+
+        let _file = self.file.lock().expect("Auditoria envenenada");
+        let written = serde_json::to_string(&record)
+            .map_err(std::io::Error::from)
+            .and_then(|line| {
+                let mut file = OpenOptions::new().create(true).append(true).open(&self.path)?;
+                writeln!(file, "{}", secret::redact(&line))
+            });
+        if let Err(error) = written {
+            warn!(exception = format!("{:?}", error), path = self.path.to_str(), "Não foi possivel gravar a auditoria");
+        }
+    }
+}
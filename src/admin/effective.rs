@@ -0,0 +1,69 @@
+use std::path::PathBuf;
+
+use color_eyre::Result;
+use serde_json::{Value, json};
+
+use crate::{applications::Application, health, management::State, reload::Runtime, secret};
+
+use super::store::Source;
+
+/// Configuração efetiva da versão atual, com os segredos mascarados.
+///
+/// O resumo e o momento da carga permitem conferir qual configuração cada instância está rodando.
+pub(crate) fn describe(runtime: &Runtime) -> Result<Value> {
+    let state = runtime.state();
+    let source = match runtime.source() {
+        Some(path) => json!({ "type": "file", "path": path, "files": state.files() }),
+        None => json!({ "type": "env" }),
+    };
+    let applications = state.catalog().iter()
+        .map(|entry| match state.apps().iter().find(|application| application.domain() == entry.name) {
+            Some(application) => application_json(&state, application, entry.source),
+            None => Ok(json!({ "name": entry.name, "source": entry.source, "disabled": entry.disabled })),
+        })
+        .collect::<Result<Vec<Value>>>()?;
+    let listeners = state.listeners().iter()
+        .map(|listener| json!({ "name": listener.name, "bind": listener.describe(), "tls": state.tls().is_some() && listener.uses_tls() }))
+        .collect::<Vec<Value>>();
+
+    Ok(json!({
+        "version": health::version(),
+        "source": source,
+        "hash": state.hash(),
+        "loaded_at": state.loaded_at(),
+        "applications": applications,
+        "listeners": listeners,
+        "reloads": runtime.history(),
+        "document": secret::redact_value(state.document())
+    }))
+}
+
+/// Aplicação com a guarda resolvida para cada rota citada na configuração; `null` é rota pública.
+fn application_json(state: &State, application: &Application, source: Source) -> Result<Value> {
+    let gate = state.gate(application)?;
+    let mut routes = application.routes().cloned().collect::<Vec<PathBuf>>();
+    routes.sort();
+    routes.dedup();
+    let routes = routes.iter()
+        .map(|route| {
+            let guard = match gate.route_guards.get(route) {
+                Some(guard) => Some(guard.name()),
+                None if application.is_unauthenticaded(route) => None,
+                None => Some(gate.guard.name()),
+            };
+            json!({ "route": route, "guard": guard })
+        })
+        .collect::<Vec<Value>>();
+
+    Ok(json!({
+        "name": application.domain(),
+        "source": source,
+        "disabled": false,
+        "prefix": format!("/{}/", application.domain()),
+        "upstream": secret::redact(application.endpoint()),
+        "guard": gate.guard.name(),
+        "enforcement": application.enforcement(),
+        "listeners": application.listeners(),
+        "routes": routes
+    }))
+}
@@ -0,0 +1,257 @@
+use std::{collections::HashMap, net::SocketAddr, path::PathBuf, sync::Arc};
+
+use axum::{
+    Router, Json, Extension,
+    body::Body,
+    extract::Path,
+    http::{Request, StatusCode, header::AUTHORIZATION},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{get, post, put},
+};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::warn;
+
+use crate::{gateway::{guard::unauthorized_response, response::ProxyResponse}, reload::{Runtime, Trigger}, secret::{self, Secret}};
+
+use self::{audit::Audit, store::{Entry, Overlay}};
+
+pub(crate) mod store;
+mod audit;
+mod effective;
+
+const DEFAULT_PREFIX: &str = "/_admin";
+const DEFAULT_STORAGE: &str = "/storage";
+const UNAUTHENTICATED_ROUTES: &str = "unauthenticated_routes";
+
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct AdminSettings {
+    /// Endereço próprio para a API; sem ele, a API fica sob `prefix` nos listeners do gateway.
+    pub address: Option<SocketAddr>,
+    /// Se o endereço próprio termina TLS, como o `tls` dos listeners.
+    pub tls: Option<bool>,
+    #[serde(default = "default_prefix")]
+    pub prefix: String,
+    /// Tokens aceitos, pela identidade do administrador registrada na auditoria.
+    pub tokens: HashMap<String, Secret>,
+    /// Diretório onde ficam as alterações e a auditoria.
+    #[serde(default = "default_storage")]
+    pub storage: PathBuf
+}
+
+fn default_prefix() -> String {
+    DEFAULT_PREFIX.to_string()
+}
+
+fn default_storage() -> PathBuf {
+    PathBuf::from(DEFAULT_STORAGE)
+}
+
+/// Administrador autenticado na requisição.
+#[derive(Clone)]
+struct Identity(String);
+
+pub(crate) struct Admin {
+    settings: AdminSettings,
+    runtime: Arc<Runtime>,
+    audit: Audit
+}
+
+impl Admin {
+    pub(crate) fn new(settings: AdminSettings, runtime: Arc<Runtime>) -> Self {
+        let audit = Audit::new(&settings.storage);
+        Admin { settings, runtime, audit }
+    }
+
+    /// A comparação com cada token é feita em tempo constante pelo próprio `Secret`.
+    fn identify(&self, token: &str) -> Option<Identity> {
+        self.settings.tokens.iter()
+            .find(|(_, known)| *known == token)
+            .map(|(identity, _)| Identity(identity.clone()))
+    }
+
+    fn entry(&self, name: &str) -> Option<Entry> {
+        let name = name.to_lowercase();
+        self.runtime.state().catalog().iter().find(|entry| entry.name == name).cloned()
+    }
+
+    /// Aplica uma alteração: valida montando a nova versão, persiste e só então troca a versão atual.
+    ///
+    /// Uma alteração recusada não muda nada, nem no roteamento nem no armazenamento.
+    async fn change<F>(&self, identity: &Identity, action: &str, name: &str, mutate: F) -> Result<Option<Entry>, ProxyResponse>
+    where F: FnOnce(&mut Overlay, Option<&Entry>) -> Result<(), ProxyResponse>
+    {
+        let name = name.to_lowercase();
+        let _exclusive = self.runtime.exclusive().await;
+        let rejected = |detail: &str| self.audit.record(&identity.0, action, &name, Err(detail));
+
+        let mut overlay = match Overlay::read(&self.settings.storage) {
+            Ok(overlay) => overlay,
+            Err(error) => {
+                rejected(&error.to_string());
+                return Err(storage_response());
+            },
+        };
+        if let Err(response) = mutate(&mut overlay, self.entry(&name).as_ref()) {
+            rejected(&format!("status {}", response.status().as_u16()));
+            return Err(response);
+        }
+
+        let generation = match self.runtime.build(Some(&overlay)) {
+            Ok(generation) => generation,
+            Err(error) => {
+                self.runtime.record(Trigger::Admin, Err(&error));
+                rejected(&error.to_string());
+                return Err(ProxyResponse::error(&secret::redact(&error.to_string()), 20, StatusCode::UNPROCESSABLE_ENTITY));
+            },
+        };
+        if let Err(error) = overlay.save(&self.settings.storage) {
+            rejected(&error.to_string());
+            return Err(storage_response());
+        }
+
+        let entry = generation.state.catalog().iter().find(|entry| entry.name == name).cloned();
+        self.runtime.record(Trigger::Admin, Ok(&generation.state));
+        self.runtime.swap(generation);
+        self.audit.record(&identity.0, action, &name, Ok(()));
+        Ok(entry)
+    }
+}
+
+/// Rotas da API de administração, todas autenticadas por token.
+pub(crate) fn router(admin: Arc<Admin>) -> Router {
+    let authenticator = admin.clone();
+    Router::new()
+        .route("/applications", get(list).post(create))
+        .route("/applications/:name", get(show).put(update).delete(remove))
+        .route("/applications/:name/disable", post(disable))
+        .route("/applications/:name/enable", post(enable))
+        .route("/applications/:name/unauthenticated_routes/*route", put(add_route).delete(remove_route))
+        .route("/config", get(config))
+        .layer(middleware::from_fn(move |request, next| authenticate(authenticator.clone(), request, next)))
+        .layer(Extension(admin))
+}
+
+async fn authenticate(admin: Arc<Admin>, mut request: Request<Body>, next: Next<Body>) -> Response {
+    let identity = request.headers()
+        .get(AUTHORIZATION)
+        .and_then(|header| header.to_str().ok())
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .and_then(|token| admin.identify(token.trim()));
+
+    match identity {
+        Some(identity) => {
+            request.extensions_mut().insert(identity);
+            next.run(request).await
+        },
+        None => {
+            warn!(path = request.uri().path(), method = request.method().as_str(), "Acesso negado à API de administração");
+            unauthorized_response().into_response()
+        },
+    }
+}
+
+async fn config(Extension(admin): Extension<Arc<Admin>>) -> Result<Json<Value>, ProxyResponse> {
+    effective::describe(&admin.runtime)
+        .map(Json)
+        .map_err(|error| ProxyResponse::error(&secret::redact(&error.to_string()), 20, StatusCode::INTERNAL_SERVER_ERROR))
+}
+
+async fn list(Extension(admin): Extension<Arc<Admin>>) -> Json<Vec<Entry>> {
+    Json(admin.runtime.state().catalog().to_vec())
+}
+
+async fn show(Extension(admin): Extension<Arc<Admin>>, Path(name): Path<String>) -> Result<Json<Entry>, ProxyResponse> {
+    admin.entry(&name).map(Json).ok_or_else(not_found_response)
+}
+
+async fn create(Extension(admin): Extension<Arc<Admin>>, Extension(identity): Extension<Identity>, Json(definition): Json<Value>) -> Result<(StatusCode, Json<Option<Entry>>), ProxyResponse> {
+    let name = store::name(&definition);
+    let entry = admin.change(&identity, "create", &name, |overlay, existing| match existing {
+        Some(_) => Err(ProxyResponse::error("Aplicação já existe!", 19, StatusCode::CONFLICT)),
+        None => {
+            overlay.put(&name, definition);
+            Ok(())
+        },
+    }).await?;
+    Ok((StatusCode::CREATED, Json(entry)))
+}
+
+async fn update(Extension(admin): Extension<Arc<Admin>>, Extension(identity): Extension<Identity>, Path(name): Path<String>, Json(definition): Json<Value>) -> Result<Json<Option<Entry>>, ProxyResponse> {
+    if store::name(&definition) != name.to_lowercase() {
+        return Err(ProxyResponse::error("Nome da aplicação diferente do caminho!", 20, StatusCode::UNPROCESSABLE_ENTITY));
+    }
+
+    let entry = admin.change(&identity, "update", &name, |overlay, existing| {
+        existing.ok_or_else(not_found_response)?;
+        overlay.put(&name.to_lowercase(), definition);
+        Ok(())
+    }).await?;
+    Ok(Json(entry))
+}
+
+async fn remove(Extension(admin): Extension<Arc<Admin>>, Extension(identity): Extension<Identity>, Path(name): Path<String>) -> Result<StatusCode, ProxyResponse> {
+    admin.change(&identity, "delete", &name, |overlay, existing| {
+        overlay.delete(&existing.ok_or_else(not_found_response)?.name);
+        Ok(())
+    }).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn disable(Extension(admin): Extension<Arc<Admin>>, Extension(identity): Extension<Identity>, Path(name): Path<String>) -> Result<Json<Option<Entry>>, ProxyResponse> {
+    toggle(admin, identity, name, true).await
+}
+
+async fn enable(Extension(admin): Extension<Arc<Admin>>, Extension(identity): Extension<Identity>, Path(name): Path<String>) -> Result<Json<Option<Entry>>, ProxyResponse> {
+    toggle(admin, identity, name, false).await
+}
+
+async fn toggle(admin: Arc<Admin>, identity: Identity, name: String, disabled: bool) -> Result<Json<Option<Entry>>, ProxyResponse> {
+    let action = if disabled { "disable" } else { "enable" };
+    let entry = admin.change(&identity, action, &name, |overlay, existing| {
+        overlay.set_disabled(&existing.ok_or_else(not_found_response)?.name, disabled);
+        Ok(())
+    }).await?;
+    Ok(Json(entry))
+}
+
+async fn add_route(Extension(admin): Extension<Arc<Admin>>, Extension(identity): Extension<Identity>, Path((name, route)): Path<(String, String)>) -> Result<Json<Option<Entry>>, ProxyResponse> {
+    edit_routes(admin, identity, name, "add_unauthenticated_route", |routes| {
+        if !routes.contains(&Value::String(route.clone())) {
+            routes.push(Value::String(route));
+        }
+    }).await
+}
+
+async fn remove_route(Extension(admin): Extension<Arc<Admin>>, Extension(identity): Extension<Identity>, Path((name, route)): Path<(String, String)>) -> Result<Json<Option<Entry>>, ProxyResponse> {
+    edit_routes(admin, identity, name, "remove_unauthenticated_route", |routes| {
+        routes.retain(|existing| existing.as_str() != Some(route.as_str()));
+    }).await
+}
+
+async fn edit_routes<F>(admin: Arc<Admin>, identity: Identity, name: String, action: &str, edit: F) -> Result<Json<Option<Entry>>, ProxyResponse>
+where F: FnOnce(&mut Vec<Value>)
+{
+    let entry = admin.change(&identity, action, &name, |overlay, existing| {
+        let existing = existing.ok_or_else(not_found_response)?;
+        let mut definition = existing.definition.clone();
+        let routes = definition.as_object_mut()
+            .map(|definition| definition.entry(UNAUTHENTICATED_ROUTES).or_insert_with(|| Value::Array(vec![])))
+            .and_then(Value::as_array_mut)
+            .ok_or_else(|| ProxyResponse::error("Rotas não autenticadas invalidas!", 20, StatusCode::UNPROCESSABLE_ENTITY))?;
+        edit(routes);
+        overlay.put(&existing.name, definition);
+        Ok(())
+    }).await?;
+    Ok(Json(entry))
+}
+
+fn not_found_response() -> ProxyResponse {
+    ProxyResponse::error("Aplicação não encontrada!", 18, StatusCode::NOT_FOUND)
+}
+
+fn storage_response() -> ProxyResponse {
+    ProxyResponse::error("Não foi possivel acessar o armazenamento!", 21, StatusCode::INTERNAL_SERVER_ERROR)
+}
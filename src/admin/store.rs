@@ -0,0 +1,110 @@
+use std::{collections::{BTreeMap, BTreeSet}, fs, io::ErrorKind, path::Path};
+
+use color_eyre::{Result, eyre::eyre};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::secret;
+
+const OVERLAY_FILE: &str = "applications.json";
+
+/// De onde veio a definição de uma aplicação.
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Source {
+    Config,
+    Admin
+}
+
+/// Aplicação como a API de administração enxerga, incluindo as desativadas.
+#[derive(Clone, Serialize)]
+pub(crate) struct Entry {
+    pub name: String,
+    pub source: Source,
+    pub disabled: bool,
+    #[serde(serialize_with = "secret::redacted")]
+    pub definition: Value
+}
+
+/// Alterações feitas pela API de administração sobre as aplicações da configuração.
+///
+/// Fica em `applications.json` no diretório de armazenamento e é reaplicada a cada carga,
+/// inclusive nos recarregamentos da configuração.
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub(crate) struct Overlay {
+    /// Aplicações criadas ou alteradas, pelo nome.
+    #[serde(default)]
+    applications: BTreeMap<String, Value>,
+    #[serde(default)]
+    disabled: BTreeSet<String>,
+    /// Aplicações removidas pela API.
+    #[serde(default)]
+    deleted: BTreeSet<String>
+}
+
+impl Overlay {
+    pub(crate) fn read(storage: &Path) -> Result<Self> {
+        let path = storage.join(OVERLAY_FILE);
+        match fs::read_to_string(&path) {
+            Ok(content) => serde_json::from_str(&content).map_err(|error| eyre!("Não foi possivel ler {}: {}", path.display(), error)),
+            Err(error) if error.kind() == ErrorKind::NotFound => Ok(Overlay::default()),
+            Err(error) => Err(eyre!("Não foi possivel ler {}: {}", path.display(), error)),
+        }
+    }
+
+    /// Grava num arquivo temporário e renomeia, para nunca deixar o arquivo pela metade.
+    pub(crate) fn save(&self, storage: &Path) -> Result<()> {
+        let path = storage.join(OVERLAY_FILE);
+        let temporary = storage.join(format!("{}.tmp", OVERLAY_FILE));
+        fs::create_dir_all(storage)?;
+        fs::write(&temporary, serde_json::to_vec_pretty(self)?)?;
+        fs::rename(&temporary, &path)?;
+        Ok(())
+    }
+
+    /// Combina as aplicações da configuração com as alterações da API.
+    pub(crate) fn apply(&self, applications: Vec<Value>) -> Vec<Entry> {
+        let mut entries: Vec<Entry> = applications.into_iter()
+            .filter(|definition| {
+                let name = name(definition);
+                !self.deleted.contains(&name) && !self.applications.contains_key(&name)
+            })
+            .map(|definition| Entry { name: name(&definition), source: Source::Config, disabled: false, definition })
+            .collect();
+        entries.extend(self.applications.iter().map(|(name, definition)| Entry {
+            name: name.clone(),
+            source: Source::Admin,
+            disabled: false,
+            definition: definition.clone()
+        }));
+
+        for entry in entries.iter_mut() {
+            entry.disabled = self.disabled.contains(&entry.name);
+        }
+        entries
+    }
+
+    pub(crate) fn put(&mut self, name: &str, definition: Value) {
+        self.deleted.remove(name);
+        self.applications.insert(name.to_string(), definition);
+    }
+
+    pub(crate) fn set_disabled(&mut self, name: &str, disabled: bool) {
+        match disabled {
+            true => self.disabled.insert(name.to_string()),
+            false => self.disabled.remove(name),
+        };
+    }
+
+    /// Também marca como removida, para que uma aplicação da configuração alterada pela API não reapareça.
+    pub(crate) fn delete(&mut self, name: &str) {
+        self.applications.remove(name);
+        self.disabled.remove(name);
+        self.deleted.insert(name.to_string());
+    }
+}
+
+/// Nome da aplicação como usado no roteamento, ou vazio quando a definição não tem nome.
+pub(crate) fn name(definition: &Value) -> String {
+    definition.get("name").and_then(Value::as_str).unwrap_or_default().to_lowercase()
+}
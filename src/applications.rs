@@ -1,17 +1,89 @@
-use std::{slice::Iter, path::PathBuf};
+use std::{slice::Iter, path::PathBuf, collections::HashMap};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
+use crate::gateway::{guard::{GUARDIAN, token::TokenSource}, access::AccessList, cors::CorsPolicy, limit::RateLimit, bulkhead::ConcurrencyLimit, size::SizeLimits, filter::Rule};
+
+/// Estratégia de guarda de uma aplicação: o nome de uma guarda ou uma cadeia `any`/`all`.
 #[derive(Clone, Deserialize)]
+#[serde(untagged)]
+pub enum GuardStrategy {
+    Named(String),
+    Any { any: Vec<GuardStrategy> },
+    All { all: Vec<GuardStrategy> }
+}
+
+impl GuardStrategy {
+    /// Nomes de todas as guardas citadas pela estratégia.
+    pub fn names(&self) -> Vec<&str> {
+        match self {
+            GuardStrategy::Named(name) => vec![name.as_str()],
+            GuardStrategy::Any { any: strategies } | GuardStrategy::All { all: strategies } => {
+                strategies.iter().flat_map(|strategy| strategy.names()).collect()
+            },
+        }
+    }
+}
+
+impl Default for GuardStrategy {
+    fn default() -> Self {
+        GuardStrategy::Named(GUARDIAN.to_string())
+    }
+}
+
+/// Como as negações das guardas são tratadas: `observe` registra a negação e repassa a requisição,
+/// permitindo ligar a proteção de uma aplicação sem afetar os clientes.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Enforcement {
+    #[default]
+    Enforce,
+    Observe,
+    Off
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct Application {
     name: String,
     url: String,
-    unauthenticated_routes: Vec<PathBuf>
+    unauthenticated_routes: Vec<PathBuf>,
+    #[serde(default)]
+    fail_open_routes: Vec<PathBuf>,
+    #[serde(default)]
+    guard: GuardStrategy,
+    #[serde(default)]
+    route_guards: HashMap<PathBuf, GuardStrategy>,
+    /// Substitui a url da guarda `guardian`; guardas nomeadas do tipo guardian mantêm a própria.
+    guardian_url: Option<String>,
+    #[serde(default = "TokenSource::defaults")]
+    token_sources: Vec<TokenSource>,
+    #[serde(default)]
+    access: AccessList,
+    #[serde(default)]
+    route_access: HashMap<PathBuf, AccessList>,
+    cors: Option<CorsPolicy>,
+    rate_limit: Option<RateLimit>,
+    #[serde(default)]
+    route_rate_limits: HashMap<PathBuf, RateLimit>,
+    concurrency: Option<ConcurrencyLimit>,
+    #[serde(default)]
+    size_limits: SizeLimits,
+    /// Os padrões de `path` são relativos à aplicação, sem o prefixo `/<aplicação>`.
+    #[serde(default)]
+    rules: Vec<Rule>,
+    #[serde(default)]
+    enforcement: Enforcement,
+    /// Listeners que atendem a aplicação; vazio atende em todos.
+    #[serde(default)]
+    listeners: Vec<String>,
+    /// Caminho no upstream verificado periodicamente; sem ele, a raiz.
+    health_path: Option<String>
 }
 
 impl Application {
-    pub fn new(name: String, url: String, unauthenticated_routes: Vec<PathBuf>) -> Self {
-        Application { name, url, unauthenticated_routes }
+    pub fn new(name: String, url: String, unauthenticated_routes: Vec<PathBuf>, guard: GuardStrategy, guardian_url: Option<String>) -> Self {
+        Application { name, url, unauthenticated_routes, fail_open_routes: vec![], guard, route_guards: HashMap::new(), guardian_url, token_sources: TokenSource::defaults(), access: AccessList::default(), route_access: HashMap::new(), cors: None, rate_limit: None, route_rate_limits: HashMap::new(), concurrency: None, size_limits: SizeLimits::default(), rules: vec![], enforcement: Enforcement::default(), listeners: vec![], health_path: None }
     }
 
     pub fn domain(&self) -> String {
@@ -22,9 +94,93 @@ impl Application {
         &self.url
     }
 
+    pub fn unauthenticated_routes(&self) -> &[PathBuf] {
+        &self.unauthenticated_routes
+    }
+
+    pub fn guard(&self) -> &GuardStrategy {
+        &self.guard
+    }
+
+    /// Guardas especificas de rota, como a verificação de assinatura de webhooks.
+    pub fn route_guards(&self) -> &HashMap<PathBuf, GuardStrategy> {
+        &self.route_guards
+    }
+
+    pub fn enforcement(&self) -> Enforcement {
+        self.enforcement
+    }
+
+    pub fn guardian_url(&self) -> Option<&str> {
+        self.guardian_url.as_deref()
+    }
+
+    pub fn token_sources(&self) -> &[TokenSource] {
+        &self.token_sources
+    }
+
+    pub fn access(&self) -> &AccessList {
+        &self.access
+    }
+
+    pub fn route_access(&self, route: &PathBuf) -> Option<&AccessList> {
+        self.route_access.get(route)
+    }
+
+    pub fn cors(&self) -> Option<&CorsPolicy> {
+        self.cors.as_ref()
+    }
+
+    pub fn rate_limit(&self) -> Option<&RateLimit> {
+        self.rate_limit.as_ref()
+    }
+
+    pub fn route_rate_limits(&self) -> &HashMap<PathBuf, RateLimit> {
+        &self.route_rate_limits
+    }
+
+    pub fn concurrency(&self) -> Option<&ConcurrencyLimit> {
+        self.concurrency.as_ref()
+    }
+
+    pub fn size_limits(&self) -> &SizeLimits {
+        &self.size_limits
+    }
+
+    pub fn rules(&self) -> &[Rule] {
+        &self.rules
+    }
+
+    pub fn health_path(&self) -> Option<&str> {
+        self.health_path.as_deref()
+    }
+
+    pub fn listeners(&self) -> &[String] {
+        &self.listeners
+    }
+
+    /// Aplicações presas a listeners não são atendidas em conexões de outros listeners.
+    pub fn is_served_on(&self, listener: Option<&str>) -> bool {
+        self.listeners.is_empty() || listener.is_some_and(|listener| self.listeners.iter().any(|name| name == listener))
+    }
+
+    /// Todas as rotas citadas na configuração da aplicação.
+    pub fn routes(&self) -> impl Iterator<Item = &PathBuf> {
+        self.unauthenticated_routes.iter()
+            .chain(&self.fail_open_routes)
+            .chain(self.route_guards.keys())
+            .chain(self.route_access.keys())
+            .chain(self.route_rate_limits.keys())
+    }
+
     pub fn is_unauthenticaded(&self, route: &PathBuf) -> bool {
         self.unauthenticated_routes.contains(route)
     }
+
+    /// Rotas de baixo risco que continuam acessiveis quando o Guardião está fora do ar.
+    pub fn is_fail_open(&self, route: &PathBuf) -> bool {
+        self.fail_open_routes.contains(route)
+    }
 }
 
 #[derive(Clone, Deserialize)]
@@ -34,4 +190,4 @@ impl Applications {
     pub fn iter(&self) -> Iter<'_, Application> {
         self.0.iter()
     }
-}
\ No newline at end of file
+}
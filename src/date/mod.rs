@@ -1,6 +1,7 @@
 use chrono::DateTime as ChronoDateTime;
 use serde::{ Serialize};
 
+#[derive(Clone)]
 pub(crate) struct DateTime(ChronoDateTime<chrono::Local>);
 
 impl DateTime {
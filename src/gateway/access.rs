@@ -0,0 +1,154 @@
+use std::net::IpAddr;
+
+use axum::http::HeaderMap;
+use ipnet::IpNet;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use tracing::warn;
+
+use super::{request::ProxyRequest, response::ProxyResponse};
+
+const FORWARDED_FOR: &str = "X-Forwarded-For";
+
+/// Faixas de IP liberadas e bloqueadas. O bloqueio sempre vence, e uma lista de liberação vazia libera todos.
+#[derive(Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AccessList {
+    #[serde(default)]
+    allow: Vec<IpNet>,
+    #[serde(default)]
+    deny: Vec<IpNet>
+}
+
+impl AccessList {
+    fn permits(&self, ip: Option<IpAddr>) -> bool {
+        match ip {
+            Some(ip) => !self.deny.iter().any(|net| net.contains(&ip))
+                && (self.allow.is_empty() || self.allow.iter().any(|net| net.contains(&ip))),
+            None => self.allow.is_empty(),
+        }
+    }
+}
+
+/// Resolve o IP real do cliente. O `X-Forwarded-For` só é considerado quando a conexão vem de um proxy confiavel.
+///
+/// A cadeia é lida da direita para a esquerda, pulando os proxies confiaveis. Uma entrada invalida encerra
+/// a leitura sem IP, já que tudo à esquerda dela pode ter sido forjado pelo cliente.
+///
+/// Sem `remote` a conexão veio de um socket Unix, que só processos locais alcançam: o outro lado é
+/// tratado como proxy confiavel.
+pub(crate) fn client_ip(remote: Option<IpAddr>, headers: &HeaderMap, trusted_proxies: &[IpNet]) -> Option<IpAddr> {
+    let trusted = |ip: &IpAddr| trusted_proxies.iter().any(|net| net.contains(ip));
+    if let Some(remote) = remote.filter(|remote| !trusted(remote)) {
+        return Some(remote);
+    }
+
+    let hops = headers.get_all(FORWARDED_FOR)
+        .iter()
+        .flat_map(|header| match header.to_str() {
+            Ok(header) => header.split(',').map(|hop| hop.trim().parse::<IpAddr>().ok()).collect(),
+            Err(_) => vec![None],
+        })
+        .collect::<Vec<Option<IpAddr>>>();
+
+    let mut client = remote;
+    for hop in hops.into_iter().rev() {
+        match hop {
+            Some(ip) if trusted(&ip) => client = Some(ip),
+            Some(ip) => return Some(ip),
+            None => return None,
+        }
+    }
+    client
+}
+
+pub(crate) fn check(request: &ProxyRequest) -> Result<(), ProxyResponse> {
+    let application = &request.application;
+    let route = application.route_access(&request.path);
+    if application.access().permits(request.client_ip) && route.map(|list| list.permits(request.client_ip)).unwrap_or(true) {
+        return Ok(());
+    }
+
+    warn!(
+        application = application.domain(),
+        path = request.path.to_str(),
+        ip = request.client_ip.map(|ip| ip.to_string()),
+        "Endereço bloqueado pela lista de acesso"
+    );
+    Err(ProxyResponse::error("Acesso negado para este endereço!", 8, StatusCode::FORBIDDEN))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::HeaderValue;
+
+    use super::*;
+
+    fn headers(values: &[&str]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for value in values {
+            headers.append(FORWARDED_FOR, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    fn proxies() -> Vec<IpNet> {
+        vec!["10.0.0.0/8".parse().unwrap()]
+    }
+
+    fn ip(value: &str) -> Option<IpAddr> {
+        Some(value.parse().unwrap())
+    }
+
+    #[test]
+    fn untrusted_remote_ignores_forwarded_for() {
+        assert_eq!(client_ip(ip("203.0.113.7"), &headers(&["198.51.100.1"]), &proxies()), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn trusted_remote_uses_rightmost_untrusted_hop() {
+        let headers = headers(&["198.51.100.1, 203.0.113.7, 10.0.0.2"]);
+        assert_eq!(client_ip(ip("10.0.0.1"), &headers, &proxies()), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn multiple_headers_are_read_in_order() {
+        let headers = headers(&["198.51.100.1", "203.0.113.7"]);
+        assert_eq!(client_ip(ip("10.0.0.1"), &headers, &proxies()), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn invalid_hop_is_not_resolved_to_the_proxy() {
+        assert_eq!(client_ip(ip("10.0.0.1"), &headers(&["x"]), &proxies()), None);
+        assert_eq!(client_ip(ip("10.0.0.1"), &headers(&["203.0.113.7, x"]), &proxies()), None);
+    }
+
+    #[test]
+    fn invalid_hop_left_of_the_client_is_ignored() {
+        assert_eq!(client_ip(ip("10.0.0.1"), &headers(&["x, 203.0.113.7"]), &proxies()), ip("203.0.113.7"));
+    }
+
+    #[test]
+    fn only_trusted_hops_resolve_to_the_leftmost() {
+        assert_eq!(client_ip(ip("10.0.0.1"), &headers(&["10.0.0.3, 10.0.0.2"]), &proxies()), ip("10.0.0.3"));
+        assert_eq!(client_ip(ip("10.0.0.1"), &HeaderMap::new(), &proxies()), ip("10.0.0.1"));
+    }
+
+    #[test]
+    fn unix_socket_peer_is_a_trusted_proxy() {
+        assert_eq!(client_ip(None, &headers(&["198.51.100.1, 203.0.113.7"]), &[]), ip("203.0.113.7"));
+        assert_eq!(client_ip(None, &headers(&["203.0.113.7, 10.0.0.2"]), &proxies()), ip("203.0.113.7"));
+        assert_eq!(client_ip(None, &headers(&["x"]), &proxies()), None);
+        assert_eq!(client_ip(None, &HeaderMap::new(), &proxies()), None);
+    }
+
+    #[test]
+    fn deny_list_wins_and_unknown_ip_only_passes_without_allow_list() {
+        let list = AccessList { allow: vec!["192.168.0.0/16".parse().unwrap()], deny: vec!["192.168.1.0/24".parse().unwrap()] };
+        assert!(list.permits(ip("192.168.2.1")));
+        assert!(!list.permits(ip("192.168.1.1")));
+        assert!(!list.permits(ip("203.0.113.7")));
+        assert!(!list.permits(None));
+        assert!(AccessList::default().permits(None));
+    }
+}
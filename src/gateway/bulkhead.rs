@@ -0,0 +1,224 @@
+use std::{sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use color_eyre::{Result, eyre::eyre};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use tokio::sync::Notify;
+use tracing::warn;
+
+use super::response::ProxyResponse;
+
+const DEFAULT_QUEUE_TIMEOUT_MS: u64 = 1000;
+/// Peso do RTT mais recente na média de longo prazo usada como referência pelo limite adaptativo.
+const LONG_RTT_WEIGHT: f64 = 0.01;
+/// Quanto do novo limite calculado entra no limite atual a cada amostra.
+const SMOOTHING: f64 = 0.2;
+/// O gradiente nunca reduz o limite para menos da metade de uma vez.
+const MIN_GRADIENT: f64 = 0.5;
+
+/// Limite adaptativo no estilo gradiente: o limite cai quando o RTT sobe acima da média de longo prazo.
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Adaptive {
+    min_limit: usize,
+    max_limit: usize
+}
+
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConcurrencyLimit {
+    max_in_flight: usize,
+    /// Quantas requisições podem esperar por uma vaga antes de serem recusadas.
+    #[serde(default)]
+    queue: usize,
+    #[serde(default = "default_queue_timeout_ms")]
+    queue_timeout_ms: u64,
+    adaptive: Option<Adaptive>
+}
+
+fn default_queue_timeout_ms() -> u64 {
+    DEFAULT_QUEUE_TIMEOUT_MS
+}
+
+impl ConcurrencyLimit {
+    /// Limites invertidos fariam o ajuste adaptativo entrar em pânico ao liberar uma vaga.
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.max_in_flight == 0 {
+            return Err(eyre!("max_in_flight precisa ser maior que zero"));
+        }
+        match &self.adaptive {
+            Some(adaptive) if adaptive.min_limit == 0 || adaptive.min_limit > adaptive.max_limit => {
+                Err(eyre!("Limite adaptativo precisa de 0 < min_limit <= max_limit, recebido {} e {}", adaptive.min_limit, adaptive.max_limit))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    /// O limite inicial já respeita os extremos do limite adaptativo.
+    fn initial_limit(&self) -> f64 {
+        let limit = self.max_in_flight as f64;
+        match &self.adaptive {
+            Some(adaptive) => limit.clamp(adaptive.min_limit as f64, adaptive.max_limit as f64),
+            None => limit,
+        }
+    }
+}
+
+struct Occupancy {
+    in_flight: usize,
+    waiting: usize,
+    limit: f64,
+    long_rtt: Option<f64>
+}
+
+/// Isola as requisições em andamento de uma aplicação, para que um backend lento não consuma o gateway todo.
+pub(crate) struct Bulkhead {
+    settings: ConcurrencyLimit,
+    occupancy: Mutex<Occupancy>,
+    released: Notify
+}
+
+/// Vaga ocupada por uma requisição. Ao ser descartada libera a vaga e alimenta o limite adaptativo.
+pub(crate) struct Permit {
+    bulkhead: Arc<Bulkhead>,
+    started: Instant
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.bulkhead.release(self.started.elapsed());
+    }
+}
+
+/// Lugar na fila de espera, devolvido a cada volta e também quando o cliente desiste no meio da espera.
+struct Queued<'a>(&'a Bulkhead);
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        self.0.occupancy.lock().expect("Bulkhead envenenado").waiting -= 1;
+    }
+}
+
+impl Bulkhead {
+    pub(crate) fn new(settings: ConcurrencyLimit) -> Self {
+        let limit = settings.initial_limit();
+        Bulkhead {
+            settings,
+            occupancy: Mutex::new(Occupancy { in_flight: 0, waiting: 0, limit, long_rtt: None }),
+            released: Notify::new()
+        }
+    }
+
+    pub(crate) fn settings(&self) -> &ConcurrencyLimit {
+        &self.settings
+    }
+
+    pub(crate) async fn acquire(self: &Arc<Self>, application: &str) -> Result<Permit, ProxyResponse> {
+        let deadline = Instant::now() + Duration::from_millis(self.settings.queue_timeout_ms);
+
+        loop {
+            let _queued = {
+                let mut occupancy = self.occupancy.lock().expect("Bulkhead envenenado");
+                if (occupancy.in_flight as f64) < occupancy.limit.floor() {
+                    occupancy.in_flight += 1;
+                    return Ok(Permit { bulkhead: self.clone(), started: Instant::now() });
+                }
+
+                if occupancy.waiting >= self.settings.queue || Instant::now() >= deadline {
+                    warn!(application, in_flight = occupancy.in_flight, limit = occupancy.limit as u64, "Aplicação sobrecarregada, requisição descartada");
+                    return Err(ProxyResponse::error("Aplicação sobrecarregada!", 11, StatusCode::SERVICE_UNAVAILABLE));
+                }
+
+                occupancy.waiting += 1;
+                Queued(self)
+            };
+
+            // Uma liberação entre soltar o lock e esperar fica guardada no `Notify`, então não se perde.
+            let _ = tokio::time::timeout_at(deadline.into(), self.released.notified()).await;
+        }
+    }
+
+    fn release(&self, rtt: Duration) {
+        {
+            let mut occupancy = self.occupancy.lock().expect("Bulkhead envenenado");
+            occupancy.in_flight -= 1;
+            if let Some(adaptive) = &self.settings.adaptive {
+                Self::adapt(&mut occupancy, adaptive, rtt.as_secs_f64());
+            }
+        }
+        self.released.notify_one();
+    }
+
+    fn adapt(occupancy: &mut Occupancy, adaptive: &Adaptive, rtt: f64) {
+        let long_rtt = match occupancy.long_rtt {
+            Some(long_rtt) => long_rtt * (1.0 - LONG_RTT_WEIGHT) + rtt * LONG_RTT_WEIGHT,
+            None => rtt,
+        };
+        occupancy.long_rtt = Some(long_rtt);
+
+        let gradient = if rtt > 0.0 { (long_rtt / rtt).clamp(MIN_GRADIENT, 1.0) } else { 1.0 };
+        let target = occupancy.limit * gradient + occupancy.limit.sqrt();
+        let limit = occupancy.limit * (1.0 - SMOOTHING) + target * SMOOTHING;
+        occupancy.limit = limit.clamp(adaptive.min_limit as f64, adaptive.max_limit as f64);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bulkhead() -> Arc<Bulkhead> {
+        Arc::new(Bulkhead::new(ConcurrencyLimit { max_in_flight: 1, queue: 1, queue_timeout_ms: 10_000, adaptive: None }))
+    }
+
+    #[test]
+    fn inverted_or_empty_limits_are_rejected() {
+        let limit = |max_in_flight, adaptive: Option<(usize, usize)>| ConcurrencyLimit {
+            max_in_flight, queue: 0, queue_timeout_ms: 0,
+            adaptive: adaptive.map(|(min_limit, max_limit)| Adaptive { min_limit, max_limit })
+        };
+        assert!(limit(0, None).check().is_err());
+        assert!(limit(4, Some((8, 2))).check().is_err());
+        assert!(limit(4, Some((0, 2))).check().is_err());
+        assert!(limit(4, Some((2, 8))).check().is_ok());
+        assert_eq!(limit(16, Some((2, 8))).initial_limit(), 8.0);
+    }
+
+    fn waiting(bulkhead: &Bulkhead) -> usize {
+        bulkhead.occupancy.lock().unwrap().waiting
+    }
+
+    #[tokio::test]
+    async fn abandoned_wait_frees_the_queue_slot() {
+        let bulkhead = bulkhead();
+        let _permit = bulkhead.acquire("app").await.ok().unwrap();
+
+        let waiter = tokio::spawn({
+            let bulkhead = bulkhead.clone();
+            async move { bulkhead.acquire("app").await.is_ok() }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(waiting(&bulkhead), 1);
+
+        waiter.abort();
+        let _ = waiter.await;
+        assert_eq!(waiting(&bulkhead), 0);
+    }
+
+    #[tokio::test]
+    async fn queued_request_gets_the_released_slot() {
+        let bulkhead = bulkhead();
+        let permit = bulkhead.acquire("app").await.ok().unwrap();
+
+        let waiter = tokio::spawn({
+            let bulkhead = bulkhead.clone();
+            async move { bulkhead.acquire("app").await.is_ok() }
+        });
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(bulkhead.acquire("app").await.is_err());
+
+        drop(permit);
+        assert!(waiter.await.unwrap());
+        assert_eq!(waiting(&bulkhead), 0);
+    }
+}
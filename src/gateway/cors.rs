@@ -0,0 +1,127 @@
+use axum::http::{HeaderMap, HeaderValue, header::{
+    ORIGIN, VARY, ACCESS_CONTROL_REQUEST_METHOD, ACCESS_CONTROL_REQUEST_HEADERS,
+    ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_HEADERS,
+    ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE,
+}};
+use color_eyre::{Result, eyre::eyre};
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+use tracing::warn;
+
+use super::{request::ProxyRequest, response::ProxyResponse, pattern::matches};
+
+const ANY: &str = "*";
+
+fn default_methods() -> Vec<String> {
+    ["GET", "POST", "PUT", "PATCH", "DELETE", "HEAD"].iter().map(|method| method.to_string()).collect()
+}
+
+/// Politica de CORS de uma aplicação, respondida pelo próprio gateway.
+///
+/// Origens aceitam `*` como curinga, por exemplo `https://*.exemplo.com`.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct CorsPolicy {
+    allowed_origins: Vec<String>,
+    #[serde(default = "default_methods")]
+    allowed_methods: Vec<String>,
+    #[serde(default)]
+    allowed_headers: Vec<String>,
+    #[serde(default)]
+    expose_headers: Vec<String>,
+    #[serde(default)]
+    allow_credentials: bool,
+    max_age: Option<u64>
+}
+
+/// Preflight é um OPTIONS com `Origin` e `Access-Control-Request-Method`; os demais OPTIONS são requisições comuns.
+pub(crate) fn is_preflight(request: &ProxyRequest) -> bool {
+    request.method == Method::OPTIONS
+        && request.headers.contains_key(ORIGIN)
+        && request.headers.contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+}
+
+impl CorsPolicy {
+    /// Credenciais com qualquer origem deixariam qualquer site ler respostas autenticadas do usuário.
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.allow_credentials && self.allowed_origins.iter().any(|allowed| allowed == ANY) {
+            return Err(eyre!("CORS com allow_credentials não aceita a origem '*', liste as origens permitidas"));
+        }
+        Ok(())
+    }
+
+    fn allows_origin(&self, origin: &str) -> bool {
+        self.allowed_origins.iter().any(|allowed| matches(allowed, origin))
+    }
+
+    fn allows_method(&self, method: &str) -> bool {
+        self.allowed_methods.iter().any(|allowed| allowed == ANY || allowed.eq_ignore_ascii_case(method))
+    }
+
+    fn allows_headers(&self, headers: &str) -> bool {
+        headers.split(',')
+            .map(str::trim)
+            .filter(|header| !header.is_empty())
+            .all(|header| self.allowed_headers.iter().any(|allowed| allowed == ANY || allowed.eq_ignore_ascii_case(header)))
+    }
+
+    pub(crate) fn preflight(&self, request: &ProxyRequest) -> ProxyResponse {
+        let header = |name| request.headers.get(name).and_then(|value: &HeaderValue| value.to_str().ok()).unwrap_or_default();
+        let origin = header(ORIGIN);
+        let method = header(ACCESS_CONTROL_REQUEST_METHOD);
+        let requested_headers = header(ACCESS_CONTROL_REQUEST_HEADERS);
+
+        if !self.allows_origin(origin) || !self.allows_method(method) || !self.allows_headers(requested_headers) {
+            warn!(application = request.application.domain(), origin, method, headers = requested_headers, "Preflight CORS recusado");
+            return ProxyResponse::error("Requisição CORS não permitida!", 9, StatusCode::FORBIDDEN);
+        }
+
+        let mut headers = HeaderMap::new();
+        self.allow_origin(origin, &mut headers);
+        insert(&mut headers, ACCESS_CONTROL_ALLOW_METHODS.as_str(), &self.allowed_methods.join(", "));
+        if !requested_headers.is_empty() {
+            insert(&mut headers, ACCESS_CONTROL_ALLOW_HEADERS.as_str(), requested_headers);
+        }
+        if let Some(max_age) = self.max_age {
+            insert(&mut headers, ACCESS_CONTROL_MAX_AGE.as_str(), &max_age.to_string());
+        }
+
+        ProxyResponse::new(String::new(), StatusCode::NO_CONTENT, headers)
+    }
+
+    /// Aplica os headers de CORS na resposta, substituindo os que o upstream tenha enviado.
+    pub(crate) fn apply(&self, origin: &str, headers: &mut HeaderMap) {
+        for name in [ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_EXPOSE_HEADERS] {
+            headers.remove(name);
+        }
+
+        if !self.allows_origin(origin) {
+            return;
+        }
+
+        self.allow_origin(origin, headers);
+        if !self.expose_headers.is_empty() {
+            insert(headers, ACCESS_CONTROL_EXPOSE_HEADERS.as_str(), &self.expose_headers.join(", "));
+        }
+    }
+
+    /// Com credenciais o navegador não aceita `*`, então a origem é sempre ecoada.
+    fn allow_origin(&self, origin: &str, headers: &mut HeaderMap) {
+        if self.allowed_origins.iter().any(|allowed| allowed == ANY) && !self.allow_credentials {
+            insert(headers, ACCESS_CONTROL_ALLOW_ORIGIN.as_str(), ANY);
+            return;
+        }
+
+        insert(headers, ACCESS_CONTROL_ALLOW_ORIGIN.as_str(), origin);
+        headers.append(VARY, HeaderValue::from_static("Origin"));
+        if self.allow_credentials {
+            insert(headers, ACCESS_CONTROL_ALLOW_CREDENTIALS.as_str(), "true");
+        }
+    }
+}
+
+fn insert(headers: &mut HeaderMap, name: &'static str, value: &str) {
+    if let Ok(value) = HeaderValue::from_str(value) {
+        headers.insert(name, value);
+    }
+}
@@ -0,0 +1,171 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::{
+    body::Body,
+    http::{HeaderMap, HeaderValue, Request, header::{USER_AGENT, CONTENT_TYPE}},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use percent_encoding::percent_decode_str;
+use color_eyre::{Result, eyre::eyre};
+use reqwest::{Method, StatusCode};
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use super::{response::ProxyResponse, pattern::matches};
+
+pub(crate) const TAGS_HEADER: &str = "X-Emerald-Tags";
+
+fn default_deny_status() -> u16 {
+    StatusCode::FORBIDDEN.as_u16()
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum Action {
+    /// Encerra a avaliação da lista e segue com a requisição.
+    Allow,
+    /// Encerra a avaliação e responde com o status informado, `{"deny": {}}` usa 403.
+    Deny {
+        #[serde(default = "default_deny_status")]
+        status: u16
+    },
+    /// Marca a requisição, repassando a marca ao upstream, e continua avaliando.
+    Tag(String)
+}
+
+/// Header exigido pela regra: só a presença, ou também um valor (aceita `*`).
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct HeaderMatch {
+    name: String,
+    value: Option<String>
+}
+
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct QueryMatch {
+    name: String,
+    value: Option<String>
+}
+
+/// Regra de filtragem. Todas as condições informadas precisam casar; padrões aceitam `*`.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    name: String,
+    #[serde(default)]
+    methods: Vec<String>,
+    path: Option<String>,
+    #[serde(default)]
+    headers: Vec<HeaderMatch>,
+    user_agent: Option<String>,
+    #[serde(default)]
+    query: Vec<QueryMatch>,
+    content_type: Option<String>,
+    action: Action
+}
+
+/// O que as regras enxergam da requisição.
+pub(crate) struct Inspection<'a> {
+    pub method: &'a Method,
+    pub path: &'a str,
+    pub headers: &'a HeaderMap,
+    pub query: &'a HashMap<String, String>
+}
+
+impl Rule {
+    /// Um `deny` com status de sucesso ou redirecionamento faria o bloqueio parecer uma resposta normal.
+    pub(crate) fn check(&self) -> Result<()> {
+        match &self.action {
+            Action::Deny { status } if !(400..=599).contains(status) => {
+                Err(eyre!("Regra {} com status {} invalido, use um status 4xx ou 5xx", self.name, status))
+            },
+            _ => Ok(()),
+        }
+    }
+
+    fn matches(&self, inspection: &Inspection) -> bool {
+        let header = |name: &str| inspection.headers.get(name).and_then(|value| value.to_str().ok());
+        let optional = |value: &Option<String>, found: Option<&str>| match (value, found) {
+            (None, found) => found.is_some(),
+            (Some(pattern), Some(found)) => matches(pattern, found),
+            (Some(_), None) => false,
+        };
+
+        (self.methods.is_empty() || self.methods.iter().any(|method| method.eq_ignore_ascii_case(inspection.method.as_str())))
+            && self.path.as_ref().map(|pattern| matches(pattern, inspection.path)).unwrap_or(true)
+            && self.headers.iter().all(|expected| optional(&expected.value, header(&expected.name)))
+            && self.user_agent.as_ref().map(|pattern| header(USER_AGENT.as_str()).map(|agent| matches(pattern, agent)).unwrap_or(false)).unwrap_or(true)
+            && self.query.iter().all(|expected| optional(&expected.value, inspection.query.get(&expected.name).map(String::as_str)))
+            && self.content_type.as_ref().map(|pattern| header(CONTENT_TYPE.as_str()).map(|kind| matches(pattern, kind)).unwrap_or(false)).unwrap_or(true)
+    }
+}
+
+/// Resultado da avaliação de uma lista de regras.
+pub(crate) enum Outcome {
+    Pass(Vec<String>),
+    Deny(ProxyResponse)
+}
+
+/// Avalia as regras em ordem. `allow` e `deny` encerram a lista; `tag` acumula e continua.
+pub(crate) fn evaluate(rules: &[Rule], inspection: &Inspection, scope: &str) -> Outcome {
+    let mut tags = vec![];
+    for rule in rules.iter().filter(|rule| rule.matches(inspection)) {
+        match &rule.action {
+            Action::Allow => {
+                info!(scope, rule = rule.name.as_str(), method = inspection.method.as_str(), path = inspection.path, "Regra liberou a requisição");
+                break;
+            },
+            Action::Deny { status } => {
+                warn!(scope, rule = rule.name.as_str(), method = inspection.method.as_str(), path = inspection.path, status, "Regra bloqueou a requisição");
+                let status = StatusCode::from_u16(*status).unwrap_or(StatusCode::FORBIDDEN);
+                return Outcome::Deny(ProxyResponse::error("Requisição bloqueada!", 17, status));
+            },
+            Action::Tag(tag) => {
+                info!(scope, rule = rule.name.as_str(), method = inspection.method.as_str(), path = inspection.path, tag = tag.as_str(), "Regra marcou a requisição");
+                tags.push(tag.clone());
+            },
+        }
+    }
+
+    Outcome::Pass(tags)
+}
+
+pub(crate) fn tag(headers: &mut HeaderMap, tags: &[String]) {
+    if let Ok(value) = HeaderValue::from_str(&tags.join(",")) {
+        if !tags.is_empty() {
+            headers.append(TAGS_HEADER, value);
+        }
+    }
+}
+
+/// Middleware com as regras globais, avaliado antes do roteamento para as aplicações.
+///
+/// É também onde os headers do gateway enviados pelo cliente são descartados, antes de qualquer regra ou guarda.
+///
+/// O caminho é comparado já decodificado, como o upstream o recebe, para que `%61dmin` não escape de `/app/admin*`.
+/// Caminhos com `..` são recusados, já que `/app/x/../admin` chegaria ao upstream como `/admin`.
+pub(crate) async fn global(rules: Arc<Vec<Rule>>, mut request: Request<Body>, next: Next<Body>) -> Response {
+    super::strip_gateway_headers(request.headers_mut());
+    if rules.is_empty() {
+        return next.run(request).await;
+    }
+
+    let query: HashMap<String, String> = request.uri().query()
+        .and_then(|query| serde_urlencoded::from_str(query).ok())
+        .unwrap_or_default();
+    let path = percent_decode_str(request.uri().path()).decode_utf8_lossy().into_owned();
+    if super::pattern::has_dot_segments(&path) {
+        return super::invalid_path_response().into_response();
+    }
+    let inspection = Inspection { method: request.method(), path: &path, headers: request.headers(), query: &query };
+
+    match evaluate(&rules, &inspection, "global") {
+        Outcome::Pass(tags) => {
+            tag(request.headers_mut(), &tags);
+            next.run(request).await
+        },
+        Outcome::Deny(response) => response.into_response(),
+    }
+}
@@ -0,0 +1,28 @@
+use axum::async_trait;
+
+use crate::{gateway::request::ProxyRequest, secret::Secret};
+
+use super::{Guard, Verdict, unauthorized_response};
+
+/// Libera requisições que enviam uma das chaves configuradas no header informado.
+pub(crate) struct ApiKeyGuard {
+    header: String,
+    keys: Vec<Secret>
+}
+
+impl ApiKeyGuard {
+    pub(crate) fn new(header: String, keys: Vec<Secret>) -> Self {
+        ApiKeyGuard { header, keys }
+    }
+}
+
+#[async_trait]
+impl Guard for ApiKeyGuard {
+    async fn guard(&self, request: &ProxyRequest) -> Verdict {
+        let key = request.headers.get(self.header.as_str()).and_then(|header| header.to_str().ok());
+        match key {
+            Some(key) if self.keys.iter().any(|known| known == key) => Verdict::Granted(None),
+            _ => Verdict::Denied(unauthorized_response()),
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use std::collections::HashMap;
+
+use axum::async_trait;
+
+use crate::{gateway::request::ProxyRequest, secret::Secret};
+
+use super::{Guard, Verdict, unauthorized_response};
+
+/// Autenticação HTTP Basic contra a lista de usuários configurada.
+pub(crate) struct BasicGuard {
+    users: HashMap<String, Secret>
+}
+
+impl BasicGuard {
+    pub(crate) fn new(users: HashMap<String, Secret>) -> Self {
+        BasicGuard { users }
+    }
+
+    fn credentials(request: &ProxyRequest) -> Option<(String, String)> {
+        let header = request.headers.get("Authorization")?.to_str().ok()?;
+        let encoded = header.strip_prefix("Basic ")?;
+        let decoded = String::from_utf8(base64::decode(encoded).ok()?).ok()?;
+        let (user, password) = decoded.split_once(':')?;
+        Some((user.to_string(), password.to_string()))
+    }
+}
+
+#[async_trait]
+impl Guard for BasicGuard {
+    async fn guard(&self, request: &ProxyRequest) -> Verdict {
+        match Self::credentials(request) {
+            Some((user, password)) if self.users.get(&user).is_some_and(|known| known == password.as_str()) => Verdict::Granted(None),
+            _ => Verdict::Denied(unauthorized_response()),
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use axum::http::{HeaderMap, HeaderValue};
+use serde::Deserialize;
+
+pub(crate) const SUBJECT_HEADER: &str = "X-Auth-Subject";
+pub(crate) const SCOPE_HEADER: &str = "X-Auth-Scope";
+pub(crate) const CLIENT_ID_HEADER: &str = "X-Auth-Client-Id";
+
+/// Dados do token devolvidos pelo endpoint de introspecção (RFC 7662).
+#[derive(Clone, Deserialize)]
+pub(crate) struct Claims {
+    pub active: bool,
+    pub scope: Option<String>,
+    pub sub: Option<String>,
+    pub exp: Option<i64>,
+    pub client_id: Option<String>
+}
+
+impl Claims {
+    pub(crate) fn is_expired(&self) -> bool {
+        self.exp.map(|exp| exp <= chrono::Utc::now().timestamp()).unwrap_or(false)
+    }
+
+    pub(crate) fn apply(&self, headers: &mut HeaderMap) {
+        let fields = [(SUBJECT_HEADER, &self.sub), (SCOPE_HEADER, &self.scope), (CLIENT_ID_HEADER, &self.client_id)];
+        for (header, value) in fields {
+            if let Some(value) = value.as_deref().and_then(|value| HeaderValue::from_str(value).ok()) {
+                headers.insert(header, value);
+            }
+        }
+    }
+}
@@ -0,0 +1,264 @@
+use std::{collections::HashMap, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use axum::{async_trait, http::header::HOST};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::{config, gateway::request::ProxyRequest, secret::Secret};
+
+use super::{Guard, Verdict, required_token, unauthorized_response, claims::Claims};
+
+const DEFAULT_TIMEOUT_MS: u64 = 5000;
+const DEFAULT_RETRIES: u32 = 1;
+const RETRY_BACKOFF_MS: u64 = 100;
+const DEFAULT_CACHE_TTL_SECONDS: u64 = 30;
+
+/// Credenciais do cliente usadas no modo de introspecção (RFC 7662).
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct Introspection {
+    #[serde(deserialize_with = "config::text")]
+    client_id: String,
+    client_secret: Secret,
+    /// Por quanto tempo, no máximo, um token ativo é aceito sem perguntar de novo ao Guardião.
+    /// Limita a janela em que um token revogado ainda passa; zero desliga o cache.
+    #[serde(default = "default_cache_ttl_seconds")]
+    cache_ttl_seconds: u64
+}
+
+pub(crate) fn default_cache_ttl_seconds() -> u64 {
+    DEFAULT_CACHE_TTL_SECONDS
+}
+
+impl Introspection {
+    pub(crate) fn new(client_id: String, client_secret: Secret, cache_ttl_seconds: u64) -> Self {
+        Introspection { client_id, client_secret, cache_ttl_seconds }
+    }
+}
+
+/// Claims guardados até o fim do TTL do cache ou até o `exp` do token, o que vier antes.
+struct Cached {
+    claims: Claims,
+    until: Instant
+}
+
+impl Cached {
+    fn is_valid(&self) -> bool {
+        Instant::now() < self.until && !self.claims.is_expired()
+    }
+}
+
+/// Envia ao Guardião o contexto da requisição original, no estilo forward-auth.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ForwardAuth {
+    #[serde(default)]
+    headers: Vec<String>
+}
+
+impl ForwardAuth {
+    pub(crate) fn new(headers: Vec<String>) -> Self {
+        ForwardAuth { headers }
+    }
+
+    fn apply(&self, builder: RequestBuilder, request: &ProxyRequest) -> RequestBuilder {
+        let host = request.headers.get(HOST).and_then(|host| host.to_str().ok()).unwrap_or_default();
+        let mut builder = builder
+            .header("X-Forwarded-Method", request.method.as_str())
+            .header("X-Forwarded-Uri", request.uri.to_string())
+            .header("X-Forwarded-Host", host)
+            .header("X-Forwarded-App", request.application.domain());
+
+        for name in &self.headers {
+            if let Some(value) = request.headers.get(name.as_str()) {
+                builder = builder.header(name.as_str(), value);
+            }
+        }
+
+        builder
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct GuardianDefinition {
+    pub url: String,
+    pub introspection: Option<Introspection>,
+    pub forward: Option<ForwardAuth>,
+    #[serde(default = "default_timeout_ms")]
+    pub timeout_ms: u64,
+    #[serde(default = "default_retries")]
+    pub retries: u32
+}
+
+pub(crate) fn default_timeout_ms() -> u64 {
+    DEFAULT_TIMEOUT_MS
+}
+
+pub(crate) fn default_retries() -> u32 {
+    DEFAULT_RETRIES
+}
+
+#[derive(Clone)]
+pub struct Guardian {
+    url: String,
+    introspection: Option<Introspection>,
+    forward: Option<ForwardAuth>,
+    client: reqwest::Client,
+    retries: u32,
+    /// Só a guarda `guardian` embutida troca de url pelo `guardian_url` da aplicação.
+    application_url: bool,
+    cache: Arc<Mutex<HashMap<(String, String), Cached>>>
+}
+
+impl From<GuardianDefinition> for Guardian {
+    fn from(definition: GuardianDefinition) -> Self {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(definition.timeout_ms))
+            .build()
+            .expect("Cliente HTTP do Guardião");
+
+        Guardian {
+            url: definition.url,
+            introspection: definition.introspection,
+            forward: definition.forward,
+            client,
+            retries: definition.retries,
+            application_url: false,
+            cache: Arc::new(Mutex::new(HashMap::new()))
+        }
+    }
+}
+
+impl Guardian {
+    /// Guardião global, que a aplicação pode substituir por um próprio com `guardian_url`.
+    pub(crate) fn global(definition: GuardianDefinition) -> Self {
+        Guardian { application_url: true, ..Guardian::from(definition) }
+    }
+
+    /// Guardas nomeadas em `GUARDS` mantêm a própria url.
+    fn url<'a>(&'a self, request: &'a ProxyRequest) -> &'a str {
+        match self.application_url {
+            true => request.application.guardian_url().unwrap_or(&self.url),
+            false => &self.url,
+        }
+    }
+
+    fn forward(&self, builder: RequestBuilder, request: &ProxyRequest) -> RequestBuilder {
+        match &self.forward {
+            Some(forward) => forward.apply(builder, request),
+            None => builder,
+        }
+    }
+
+    /// Envia a requisição ao Guardião, tentando de novo em falhas de rede ou respostas 5xx.
+    ///
+    /// Devolve `None` quando o Guardião não respondeu de forma utilizavel em nenhuma tentativa.
+    async fn send(&self, builder: RequestBuilder) -> Option<Response> {
+        for attempt in 0..=self.retries {
+            if attempt > 0 {
+                tokio::time::sleep(Duration::from_millis(RETRY_BACKOFF_MS * attempt as u64)).await;
+            }
+
+            let request = builder.try_clone().expect("Requisição ao Guardião sem corpo em stream");
+            match request.send().await {
+                Ok(response) if response.status().is_server_error() => {
+                    warn!(status_code = response.status().as_u16(), attempt, "Guardião respondeu com erro");
+                },
+                Ok(response) => return Some(response),
+                Err(error) => {
+                    warn!(exception = format!("{:?}", error), attempt, "Não foi possivel comunicar com o Guardião");
+                },
+            }
+        }
+
+        None
+    }
+
+    async fn validate(&self, token: String, request: &ProxyRequest) -> Verdict {
+        let builder = self.client.get(self.url(request)).bearer_auth(token);
+        match self.send(self.forward(builder, request)).await {
+            Some(response) if response.status().is_success() => Verdict::Granted(None),
+            Some(response) => {
+                warn!(status_code = response.status().as_u16(), "Autorização Negada");
+                Verdict::Denied(unauthorized_response())
+            },
+            None => Verdict::Unavailable,
+        }
+    }
+
+    async fn introspect(&self, token: String, introspection: &Introspection, request: &ProxyRequest) -> Verdict {
+        let key = (self.url(request).to_string(), token);
+        if let Some(claims) = self.cached(&key) {
+            return Verdict::Granted(Some(claims));
+        }
+
+        let builder = self.client
+            .post(self.url(request))
+            .basic_auth(&introspection.client_id, Some(introspection.client_secret.expose()))
+            .form(&[("token", key.1.as_str()), ("token_type_hint", "access_token")]);
+
+        let claims = match self.send(self.forward(builder, request)).await {
+            Some(response) if response.status().is_success() => response.json::<Claims>().await,
+            // O token vai no corpo; 401 e 403 aqui recusam as credenciais do próprio gateway.
+            Some(response) if matches!(response.status(), StatusCode::UNAUTHORIZED | StatusCode::FORBIDDEN) => {
+                warn!(status_code = response.status().as_u16(), "Credenciais de introspecção recusadas pelo Guardião");
+                return Verdict::Unavailable
+            },
+            Some(response) => {
+                warn!(status_code = response.status().as_u16(), "Autorização Negada");
+                return Verdict::Denied(unauthorized_response())
+            },
+            None => return Verdict::Unavailable,
+        };
+
+        match claims {
+            Ok(claims) if claims.active && !claims.is_expired() => {
+                self.remember(key, claims.clone(), introspection);
+                Verdict::Granted(Some(claims))
+            },
+            Ok(_) => {
+                warn!(exception = "Token inativo", "Autorização Negada");
+                Verdict::Denied(unauthorized_response())
+            },
+            Err(error) => {
+                warn!(exception = format!("{:?}", error), "Resposta de introspecção invalida");
+                Verdict::Unavailable
+            },
+        }
+    }
+
+    fn cached(&self, key: &(String, String)) -> Option<Claims> {
+        let cache = self.cache.lock().expect("Cache do Guardião envenenado");
+        cache.get(key).filter(|cached| cached.is_valid()).map(|cached| cached.claims.clone())
+    }
+
+    /// Só tokens com `exp` são guardados, e nunca por mais que `cache_ttl_seconds`, para que um token
+    /// revogado deixe de ser aceito logo. Com forward-auth a decisão depende da rota, então nada é guardado.
+    fn remember(&self, key: (String, String), claims: Claims, introspection: &Introspection) {
+        if claims.exp.is_none() || self.forward.is_some() || introspection.cache_ttl_seconds == 0 {
+            return;
+        }
+
+        let until = Instant::now() + Duration::from_secs(introspection.cache_ttl_seconds);
+        let mut cache = self.cache.lock().expect("Cache do Guardião envenenado");
+        cache.retain(|_, cached| cached.is_valid());
+        cache.insert(key, Cached { claims, until });
+    }
+}
+
+#[async_trait]
+impl Guard for Guardian {
+    async fn guard(&self, request: &ProxyRequest) -> Verdict {
+        let token = match required_token(request) {
+            Ok(token) => token,
+            Err(response) => return Verdict::Denied(response),
+        };
+
+        match &self.introspection {
+            Some(introspection) => self.introspect(token, introspection, request).await,
+            None => self.validate(token, request).await,
+        }
+    }
+}
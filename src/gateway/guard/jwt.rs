@@ -0,0 +1,80 @@
+use axum::async_trait;
+use color_eyre::{Result, eyre::eyre};
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::warn;
+
+use crate::{gateway::request::ProxyRequest, secret::Secret};
+
+use super::{Guard, Verdict, required_token, unauthorized_response, claims::Claims};
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct JwtDefinition {
+    algorithm: Algorithm,
+    secret: Option<Secret>,
+    public_key: Option<String>,
+    issuer: Option<String>,
+    audience: Option<String>
+}
+
+/// Valida tokens JWT localmente, sem consultar o Guardião.
+pub(crate) struct JwtGuard {
+    key: DecodingKey,
+    validation: Validation
+}
+
+impl JwtGuard {
+    pub(crate) fn new(definition: JwtDefinition) -> Result<Self> {
+        let key = match (definition.secret, definition.public_key) {
+            (Some(secret), _) => DecodingKey::from_secret(secret.expose().as_bytes()),
+            (None, Some(public_key)) => match definition.algorithm {
+                Algorithm::ES256 | Algorithm::ES384 => DecodingKey::from_ec_pem(public_key.as_bytes())?,
+                Algorithm::EdDSA => DecodingKey::from_ed_pem(public_key.as_bytes())?,
+                _ => DecodingKey::from_rsa_pem(public_key.as_bytes())?,
+            },
+            (None, None) => return Err(eyre!("Guarda JWT precisa de secret ou public_key")),
+        };
+
+        let mut validation = Validation::new(definition.algorithm);
+        if let Some(issuer) = definition.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        if let Some(audience) = definition.audience {
+            validation.set_audience(&[audience]);
+        }
+
+        Ok(JwtGuard { key, validation })
+    }
+}
+
+#[async_trait]
+impl Guard for JwtGuard {
+    async fn guard(&self, request: &ProxyRequest) -> Verdict {
+        let token = match required_token(request) {
+            Ok(token) => token,
+            Err(response) => return Verdict::Denied(response),
+        };
+
+        match jsonwebtoken::decode::<Value>(&token, &self.key, &self.validation) {
+            Ok(data) => Verdict::Granted(Some(claims(&data.claims))),
+            Err(error) => {
+                warn!(exception = format!("{:?}", error), "Token JWT invalido");
+                Verdict::Denied(unauthorized_response())
+            }
+        }
+    }
+}
+
+/// Claims do JWT no mesmo formato da introspecção, para os headers `X-Auth-*` e os limites por `sub`.
+fn claims(token: &Value) -> Claims {
+    let text = |name: &str| token.get(name).and_then(Value::as_str).map(String::from);
+    Claims {
+        active: true,
+        scope: text("scope"),
+        sub: text("sub"),
+        exp: token.get("exp").and_then(Value::as_i64),
+        client_id: text("client_id")
+    }
+}
@@ -0,0 +1,181 @@
+use std::{collections::HashMap, sync::Arc};
+
+use axum::async_trait;
+use color_eyre::{Result, eyre::eyre};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use tracing::{info, warn};
+
+use crate::{applications::GuardStrategy, secret::Secret};
+
+use self::{guardian::{Guardian, GuardianDefinition}, claims::Claims, jwt::{JwtGuard, JwtDefinition}, api_key::ApiKeyGuard, basic::BasicGuard, signature::{SignatureGuard, SignatureDefinition}, mtls::MtlsGuard};
+
+use super::{response::ProxyResponse, request::ProxyRequest};
+
+pub(crate) mod guardian;
+pub(crate) mod claims;
+pub(crate) mod token;
+mod jwt;
+mod api_key;
+mod basic;
+mod signature;
+mod mtls;
+
+pub(crate) const GUARDIAN: &str = "guardian";
+pub(crate) const NONE: &str = "none";
+
+pub(crate) enum Verdict {
+    Granted(Option<Claims>),
+    Denied(ProxyResponse),
+    /// A guarda não conseguiu decidir, por exemplo com o Guardião fora do ar.
+    Unavailable
+}
+
+impl Verdict {
+    pub(crate) fn describe(&self) -> &'static str {
+        match self {
+            Verdict::Granted(_) => "granted",
+            Verdict::Denied(_) => "denied",
+            Verdict::Unavailable => "unavailable",
+        }
+    }
+}
+
+#[async_trait]
+pub(crate) trait Guard: Send + Sync {
+    async fn guard(&self, request: &ProxyRequest) -> Verdict;
+}
+
+/// Declaração de uma guarda nomeada, lida da env `GUARDS`.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+pub(crate) enum GuardDefinition {
+    Guardian(GuardianDefinition),
+    Jwt(JwtDefinition),
+    ApiKey { header: String, keys: Vec<Secret> },
+    Basic { users: HashMap<String, Secret> },
+    Hmac(SignatureDefinition),
+    Mtls {
+        #[serde(default)]
+        allowed_subjects: Vec<String>,
+        #[serde(default)]
+        allowed_sans: Vec<String>
+    },
+    None
+}
+
+impl GuardDefinition {
+    pub(crate) fn build(self) -> Result<Arc<dyn Guard>> {
+        Ok(match self {
+            GuardDefinition::Guardian(definition) => Arc::new(Guardian::from(definition)),
+            GuardDefinition::Jwt(definition) => Arc::new(JwtGuard::new(definition)?),
+            GuardDefinition::ApiKey { header, keys } => Arc::new(ApiKeyGuard::new(header, keys)),
+            GuardDefinition::Basic { users } => Arc::new(BasicGuard::new(users)),
+            GuardDefinition::Hmac(definition) => Arc::new(SignatureGuard::new(definition)),
+            GuardDefinition::Mtls { allowed_subjects, allowed_sans } => Arc::new(MtlsGuard::new(allowed_subjects, allowed_sans)),
+            GuardDefinition::None => Arc::new(NoGuard),
+        })
+    }
+}
+
+pub(crate) struct NoGuard;
+
+#[async_trait]
+impl Guard for NoGuard {
+    async fn guard(&self, _request: &ProxyRequest) -> Verdict {
+        Verdict::Granted(None)
+    }
+}
+
+/// Cadeia de guardas resolvida para uma aplicação.
+///
+/// `Any` libera a requisição na primeira guarda que conceder acesso, `All` exige que todas concedam.
+#[derive(Clone)]
+pub(crate) enum GuardChain {
+    Single(String, Arc<dyn Guard>),
+    Any(Vec<GuardChain>),
+    All(Vec<GuardChain>)
+}
+
+impl GuardChain {
+    pub(crate) fn resolve(strategy: &GuardStrategy, guards: &HashMap<String, Arc<dyn Guard>>) -> Result<Self> {
+        Ok(match strategy {
+            GuardStrategy::Named(name) => {
+                let guard = guards.get(name).ok_or_else(|| eyre!("Guarda {} não foi configurada", name))?;
+                GuardChain::Single(name.clone(), guard.clone())
+            },
+            GuardStrategy::Any { any } => GuardChain::Any(Self::resolve_all(any, guards)?),
+            GuardStrategy::All { all } => GuardChain::All(Self::resolve_all(all, guards)?),
+        })
+    }
+
+    fn resolve_all(strategies: &[GuardStrategy], guards: &HashMap<String, Arc<dyn Guard>>) -> Result<Vec<Self>> {
+        strategies.iter().map(|strategy| Self::resolve(strategy, guards)).collect()
+    }
+
+    pub(crate) fn name(&self) -> String {
+        match self {
+            GuardChain::Single(name, _) => name.clone(),
+            GuardChain::Any(chains) => format!("any({})", Self::names(chains)),
+            GuardChain::All(chains) => format!("all({})", Self::names(chains)),
+        }
+    }
+
+    fn names(chains: &[GuardChain]) -> String {
+        chains.iter().map(|chain| chain.name()).collect::<Vec<String>>().join(", ")
+    }
+}
+
+#[async_trait]
+impl Guard for GuardChain {
+    async fn guard(&self, request: &ProxyRequest) -> Verdict {
+        match self {
+            GuardChain::Single(name, guard) => {
+                let verdict = guard.guard(request).await;
+                info!(application = request.application.domain(), guard = name.as_str(), result = verdict.describe(), "Estratégia de guarda avaliada");
+                verdict
+            },
+            GuardChain::Any(chains) => {
+                let mut verdict = Verdict::Denied(unauthorized_response());
+                for chain in chains {
+                    match chain.guard(request).await {
+                        Verdict::Granted(claims) => return Verdict::Granted(claims),
+                        Verdict::Unavailable => verdict = Verdict::Unavailable,
+                        Verdict::Denied(response) if !matches!(verdict, Verdict::Unavailable) => verdict = Verdict::Denied(response),
+                        Verdict::Denied(_) => (),
+                    }
+                }
+                verdict
+            },
+            GuardChain::All(chains) => {
+                let mut granted = None;
+                for chain in chains {
+                    match chain.guard(request).await {
+                        Verdict::Granted(claims) => granted = claims.or(granted),
+                        denied => return denied,
+                    }
+                }
+                Verdict::Granted(granted)
+            },
+        }
+    }
+}
+
+/// Token da requisição, lido das fontes configuradas na aplicação. A ausência de token é negada.
+pub(crate) fn required_token(request: &ProxyRequest) -> Result<String, ProxyResponse> {
+    match token::extract(request)? {
+        Some(token) => Ok(token),
+        None => {
+            warn!(exception = "Faltando token de autenticação", "Autorização Negada");
+            Err(unauthorized_response())
+        }
+    }
+}
+
+pub(crate) fn unauthorized_response() -> ProxyResponse {
+    ProxyResponse::error("Acesso não autorizado!", 5, StatusCode::UNAUTHORIZED)
+}
+
+pub(crate) fn unavailable_response() -> ProxyResponse {
+    ProxyResponse::error("Serviço de autorização indisponível!", 6, StatusCode::SERVICE_UNAVAILABLE)
+}
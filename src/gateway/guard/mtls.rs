@@ -0,0 +1,42 @@
+use axum::async_trait;
+use tracing::warn;
+
+use crate::gateway::request::ProxyRequest;
+
+use super::{Guard, Verdict, unauthorized_response};
+
+/// Exige um certificado de cliente verificado no handshake TLS.
+///
+/// Com listas vazias qualquer certificado assinado pela CA é aceito.
+pub(crate) struct MtlsGuard {
+    subjects: Vec<String>,
+    sans: Vec<String>
+}
+
+impl MtlsGuard {
+    pub(crate) fn new(subjects: Vec<String>, sans: Vec<String>) -> Self {
+        MtlsGuard { subjects, sans }
+    }
+}
+
+#[async_trait]
+impl Guard for MtlsGuard {
+    async fn guard(&self, request: &ProxyRequest) -> Verdict {
+        let certificate = match &request.connection.client_certificate {
+            Some(certificate) => certificate,
+            None => {
+                warn!(exception = "Faltando certificado de cliente", "Autorização Negada");
+                return Verdict::Denied(unauthorized_response());
+            }
+        };
+
+        let unrestricted = self.subjects.is_empty() && self.sans.is_empty();
+        let allowed = self.subjects.contains(&certificate.subject) || certificate.sans.iter().any(|san| self.sans.contains(san));
+        if !unrestricted && !allowed {
+            warn!(subject = certificate.subject.as_str(), fingerprint = certificate.fingerprint.as_str(), "Certificado de cliente não permitido");
+            return Verdict::Denied(unauthorized_response());
+        }
+
+        Verdict::Granted(None)
+    }
+}
@@ -0,0 +1,262 @@
+use axum::async_trait;
+use color_eyre::{Result, eyre::eyre};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use tracing::warn;
+
+use crate::{gateway::request::ProxyRequest, secret::Secret};
+
+use super::{Guard, Verdict, unauthorized_response};
+
+const DEFAULT_TOLERANCE_SECONDS: i64 = 300;
+
+#[derive(Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SignatureEncoding {
+    #[default]
+    Hex,
+    Base64
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct SignatureDefinition {
+    secret: Secret,
+    header: String,
+    /// Prefixo que o provedor coloca antes da assinatura, como `sha256=`.
+    prefix: Option<String>,
+    #[serde(default)]
+    encoding: SignatureEncoding,
+    /// Headers cujos valores entram na assinatura, na ordem, antes do corpo.
+    #[serde(default)]
+    signed_headers: Vec<String>,
+    timestamp_header: Option<String>,
+    #[serde(default = "default_tolerance_seconds")]
+    tolerance_seconds: i64
+}
+
+fn default_tolerance_seconds() -> i64 {
+    DEFAULT_TOLERANCE_SECONDS
+}
+
+impl SignatureDefinition {
+    /// Fora da assinatura, o timestamp poderia ser renovado por quem captura um webhook e o reenvia.
+    pub(crate) fn check(&self) -> Result<()> {
+        match &self.timestamp_header {
+            Some(header) if !self.signed_headers.iter().any(|signed| signed.eq_ignore_ascii_case(header)) => {
+                Err(eyre!("timestamp_header {} precisa estar em signed_headers", header))
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Verifica a assinatura HMAC-SHA256 de webhooks enviados por terceiros.
+///
+/// O conteúdo assinado é o valor de cada header de `signed_headers` seguido de `.`, e depois o corpo.
+pub(crate) struct SignatureGuard {
+    definition: SignatureDefinition
+}
+
+impl SignatureGuard {
+    pub(crate) fn new(definition: SignatureDefinition) -> Self {
+        SignatureGuard { definition }
+    }
+
+    fn header<'a>(request: &'a ProxyRequest, name: &str) -> Option<&'a str> {
+        request.headers.get(name).and_then(|value| value.to_str().ok())
+    }
+
+    fn signature(&self, request: &ProxyRequest) -> Option<Vec<u8>> {
+        let value = Self::header(request, &self.definition.header)?.trim();
+        let value = match &self.definition.prefix {
+            Some(prefix) => value.strip_prefix(prefix.as_str())?,
+            None => value,
+        };
+
+        match self.definition.encoding {
+            SignatureEncoding::Hex => hex::decode(value).ok(),
+            SignatureEncoding::Base64 => base64::decode(value).ok(),
+        }
+    }
+
+    fn is_fresh(&self, request: &ProxyRequest) -> bool {
+        let header = match &self.definition.timestamp_header {
+            Some(header) => header,
+            None => return true,
+        };
+
+        match Self::header(request, header).and_then(|value| value.trim().parse::<i64>().ok()) {
+            Some(timestamp) => (chrono::Utc::now().timestamp() - timestamp).abs() <= self.definition.tolerance_seconds,
+            None => false,
+        }
+    }
+
+    fn verify(&self, request: &ProxyRequest, signature: &[u8]) -> bool {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.definition.secret.expose().as_bytes()).expect("HMAC aceita chaves de qualquer tamanho");
+        for name in &self.definition.signed_headers {
+            match Self::header(request, name) {
+                Some(value) => {
+                    mac.update(value.as_bytes());
+                    mac.update(b".");
+                },
+                None => return false,
+            }
+        }
+        mac.update(&request.body);
+        mac.verify_slice(signature).is_ok()
+    }
+}
+
+#[async_trait]
+impl Guard for SignatureGuard {
+    async fn guard(&self, request: &ProxyRequest) -> Verdict {
+        let signature = match self.signature(request) {
+            Some(signature) => signature,
+            None => {
+                warn!(header = self.definition.header.as_str(), exception = "Assinatura ausente ou malformada", "Autorização Negada");
+                return Verdict::Denied(unauthorized_response());
+            }
+        };
+
+        if !self.is_fresh(request) {
+            warn!(exception = "Timestamp fora da janela de tolerância", "Autorização Negada");
+            return Verdict::Denied(unauthorized_response());
+        }
+
+        if !self.verify(request, &signature) {
+            warn!(exception = "Assinatura invalida", "Autorização Negada");
+            return Verdict::Denied(unauthorized_response());
+        }
+
+        Verdict::Granted(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use axum::{body::Bytes, extract::Query, http::{HeaderMap, HeaderValue, header::HeaderName}};
+    use reqwest::Method;
+    use serde_json::json;
+
+    use crate::server::connection::Connection;
+
+    use super::*;
+
+    const SECRET: &str = "segredo";
+
+    fn definition(definition: serde_json::Value) -> SignatureDefinition {
+        serde_json::from_value(definition).unwrap()
+    }
+
+    fn timestamped() -> SignatureDefinition {
+        definition(json!({
+            "secret": SECRET, "header": "X-Signature", "prefix": "sha256=",
+            "signed_headers": ["X-Timestamp", "X-Event"], "timestamp_header": "X-Timestamp"
+        }))
+    }
+
+    fn sign(parts: &[&str], body: &str) -> Vec<u8> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(SECRET.as_bytes()).unwrap();
+        for part in parts {
+            mac.update(part.as_bytes());
+            mac.update(b".");
+        }
+        mac.update(body.as_bytes());
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    fn request(headers: &[(&str, String)], body: &str) -> ProxyRequest {
+        let application = serde_json::from_value(json!({ "name": "app", "url": "http://localhost", "unauthenticated_routes": [] })).unwrap();
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.insert(HeaderName::from_bytes(name.as_bytes()).unwrap(), HeaderValue::from_str(value).unwrap());
+        }
+
+        ProxyRequest {
+            path: "/webhook".into(),
+            method: Method::POST,
+            headers: map,
+            body: Bytes::from(body.to_string()),
+            query: Query(HashMap::new()),
+            uri: "/app/webhook".parse().unwrap(),
+            connection: Connection::default(),
+            client_ip: None,
+            application
+        }
+    }
+
+    fn signed(timestamp: i64, event: &str, body: &str) -> Vec<(&'static str, String)> {
+        let timestamp = timestamp.to_string();
+        let signature = format!("sha256={}", hex::encode(sign(&[&timestamp, event], body)));
+        vec![("X-Timestamp", timestamp), ("X-Event", event.to_string()), ("X-Signature", signature)]
+    }
+
+    async fn granted(guard: &SignatureGuard, request: &ProxyRequest) -> bool {
+        matches!(guard.guard(request).await, Verdict::Granted(_))
+    }
+
+    #[tokio::test]
+    async fn valid_signature_is_granted() {
+        let guard = SignatureGuard::new(timestamped());
+        let now = chrono::Utc::now().timestamp();
+        assert!(granted(&guard, &request(&signed(now, "push", "{}"), "{}")).await);
+    }
+
+    #[tokio::test]
+    async fn base64_signature_without_prefix_is_granted() {
+        let guard = SignatureGuard::new(definition(json!({ "secret": SECRET, "header": "X-Signature", "encoding": "base64" })));
+        let signature = base64::encode(sign(&[], "{}"));
+        assert!(granted(&guard, &request(&[("X-Signature", signature)], "{}")).await);
+    }
+
+    #[tokio::test]
+    async fn tampered_body_is_denied() {
+        let guard = SignatureGuard::new(timestamped());
+        let now = chrono::Utc::now().timestamp();
+        assert!(!granted(&guard, &request(&signed(now, "push", "{}"), "{\"admin\":true}")).await);
+    }
+
+    #[tokio::test]
+    async fn signed_headers_are_checked_in_order() {
+        let guard = SignatureGuard::new(timestamped());
+        let now = chrono::Utc::now().timestamp();
+        let mut headers = signed(now, "push", "{}");
+        headers[2].1 = format!("sha256={}", hex::encode(sign(&["push", &now.to_string()], "{}")));
+        assert!(!granted(&guard, &request(&headers, "{}")).await);
+    }
+
+    #[tokio::test]
+    async fn missing_or_malformed_signature_is_denied() {
+        let guard = SignatureGuard::new(timestamped());
+        let now = chrono::Utc::now().timestamp();
+        let mut headers = signed(now, "push", "{}");
+        headers[2].1 = headers[2].1.trim_start_matches("sha256=").to_string();
+        assert!(!granted(&guard, &request(&headers, "{}")).await);
+        assert!(!granted(&guard, &request(&headers[..2], "{}")).await);
+    }
+
+    #[tokio::test]
+    async fn missing_timestamp_is_denied() {
+        let guard = SignatureGuard::new(timestamped());
+        let signature = format!("sha256={}", hex::encode(sign(&["push"], "{}")));
+        assert!(!granted(&guard, &request(&[("X-Event", "push".to_string()), ("X-Signature", signature)], "{}")).await);
+    }
+
+    #[tokio::test]
+    async fn stale_timestamp_is_denied() {
+        let guard = SignatureGuard::new(timestamped());
+        let stale = chrono::Utc::now().timestamp() - DEFAULT_TOLERANCE_SECONDS - 60;
+        assert!(!granted(&guard, &request(&signed(stale, "push", "{}"), "{}")).await);
+    }
+
+    #[test]
+    fn unsigned_timestamp_is_rejected() {
+        assert!(timestamped().check().is_ok());
+        let unsigned = definition(json!({ "secret": SECRET, "header": "X-Signature", "timestamp_header": "X-Timestamp" }));
+        assert!(unsigned.check().is_err());
+    }
+}
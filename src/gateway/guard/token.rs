@@ -0,0 +1,174 @@
+use axum::http::header::{AUTHORIZATION, COOKIE};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::gateway::{request::ProxyRequest, response::ProxyResponse};
+
+use super::unauthorized_response;
+
+const BEARER: &str = "Bearer";
+
+/// De onde o token de uma aplicação pode ser lido, na ordem configurada.
+#[derive(Clone, Deserialize)]
+#[serde(rename_all = "snake_case", deny_unknown_fields)]
+pub enum TokenSource {
+    Header { name: String, scheme: Option<String> },
+    Cookie { name: String },
+    Query { name: String }
+}
+
+impl TokenSource {
+    pub fn defaults() -> Vec<TokenSource> {
+        vec![TokenSource::Header { name: AUTHORIZATION.to_string(), scheme: Some(BEARER.to_string()) }]
+    }
+
+    fn extract(&self, request: &ProxyRequest) -> Result<Option<String>, ProxyResponse> {
+        match self {
+            TokenSource::Header { name, scheme } => from_header(request, name, scheme.as_deref()),
+            TokenSource::Cookie { name } => from_cookie(request, name),
+            TokenSource::Query { name } => Ok(request.query.0.get(name).cloned()),
+        }
+    }
+}
+
+/// Procura o token nas fontes da aplicação, devolvendo o primeiro encontrado.
+///
+/// Valores presentes porém malformados recusam a requisição, em vez de seguir para a próxima fonte.
+pub(crate) fn extract(request: &ProxyRequest) -> Result<Option<String>, ProxyResponse> {
+    for source in request.application.token_sources() {
+        if let Some(token) = source.extract(request)? {
+            if token.is_empty() {
+                warn!(exception = "Token vazio", "Autorização Negada");
+                return Err(unauthorized_response());
+            }
+            return Ok(Some(token));
+        }
+    }
+
+    Ok(None)
+}
+
+fn from_header(request: &ProxyRequest, name: &str, scheme: Option<&str>) -> Result<Option<String>, ProxyResponse> {
+    let value = match request.headers.get(name) {
+        Some(value) => value.to_str().map_err(|_| malformed_response(name))?,
+        None => return Ok(None),
+    };
+
+    let scheme = match scheme {
+        Some(scheme) => scheme,
+        None => return Ok(Some(value.trim().to_string())),
+    };
+
+    match value.trim().split_once(' ') {
+        Some((given, token)) if given.eq_ignore_ascii_case(scheme) => Ok(Some(token.trim().to_string())),
+        _ => {
+            warn!(header = name, exception = "Esquema de autenticação invalido", "Autorização Negada");
+            Err(unauthorized_response())
+        },
+    }
+}
+
+fn from_cookie(request: &ProxyRequest, name: &str) -> Result<Option<String>, ProxyResponse> {
+    for header in request.headers.get_all(COOKIE) {
+        let cookies = header.to_str().map_err(|_| malformed_response(COOKIE.as_str()))?;
+        let found = cookies
+            .split(';')
+            .filter_map(|cookie| cookie.trim().split_once('='))
+            .find(|(key, _)| *key == name)
+            .map(|(_, value)| value.trim_matches('"').to_string());
+
+        if found.is_some() {
+            return Ok(found);
+        }
+    }
+
+    Ok(None)
+}
+
+fn malformed_response(source: &str) -> ProxyResponse {
+    warn!(source, exception = "Credencial não é UTF-8 valido", "Requisição malformada");
+    ProxyResponse::error("Credencial malformada!", 7, StatusCode::BAD_REQUEST)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use axum::{body::Bytes, extract::Query, http::{HeaderMap, HeaderValue, header::HeaderName}};
+    use reqwest::Method;
+    use serde_json::json;
+
+    use crate::server::connection::Connection;
+
+    use super::*;
+
+    fn request(sources: serde_json::Value, headers: &[(&str, &[u8])], query: &[(&str, &str)]) -> ProxyRequest {
+        let application = serde_json::from_value(json!({
+            "name": "app", "url": "http://localhost", "unauthenticated_routes": [], "token_sources": sources
+        })).unwrap();
+        let mut map = HeaderMap::new();
+        for (name, value) in headers {
+            map.append(HeaderName::from_bytes(name.as_bytes()).unwrap(), HeaderValue::from_bytes(value).unwrap());
+        }
+
+        ProxyRequest {
+            path: "/".into(),
+            method: Method::GET,
+            headers: map,
+            body: Bytes::new(),
+            query: Query(query.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect::<HashMap<_, _>>()),
+            uri: "/app/".parse().unwrap(),
+            connection: Connection::default(),
+            client_ip: None,
+            application
+        }
+    }
+
+    fn bearer() -> serde_json::Value {
+        json!([{ "header": { "name": "Authorization", "scheme": "Bearer" } }])
+    }
+
+    #[test]
+    fn header_with_scheme() {
+        assert_eq!(extract(&request(bearer(), &[("Authorization", b"Bearer abc")], &[])).ok().flatten().as_deref(), Some("abc"));
+        assert_eq!(extract(&request(bearer(), &[("Authorization", b"bearer  abc ")], &[])).ok().flatten().as_deref(), Some("abc"));
+        assert_eq!(extract(&request(bearer(), &[], &[])).ok().flatten(), None);
+    }
+
+    #[test]
+    fn header_with_wrong_or_missing_scheme_is_unauthorized() {
+        for value in [&b"Basic abc"[..], b"abc", b"Bearer", b"Bearer "] {
+            let response = extract(&request(bearer(), &[("Authorization", value)], &[])).err().unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    #[test]
+    fn header_that_is_not_utf8_is_a_bad_request() {
+        let response = extract(&request(bearer(), &[("Authorization", b"Bearer \xff")], &[])).err().unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn header_without_scheme_rejects_empty_token() {
+        let sources = json!([{ "header": { "name": "X-Token" } }]);
+        assert_eq!(extract(&request(sources.clone(), &[("X-Token", b" abc ")], &[])).ok().flatten().as_deref(), Some("abc"));
+        assert!(extract(&request(sources, &[("X-Token", b"")], &[])).is_err());
+    }
+
+    #[test]
+    fn cookie_is_matched_by_exact_name() {
+        let sources = json!([{ "cookie": { "name": "session" } }]);
+        let headers: [(&str, &[u8]); 2] = [("Cookie", b"old_session=x; theme=dark"), ("Cookie", b"session=\"abc\"")];
+        assert_eq!(extract(&request(sources.clone(), &headers, &[])).ok().flatten().as_deref(), Some("abc"));
+        assert_eq!(extract(&request(sources, &[("Cookie", b"old_session=x")], &[])).ok().flatten(), None);
+    }
+
+    #[test]
+    fn sources_are_tried_in_order() {
+        let sources = json!([{ "query": { "name": "token" } }, { "header": { "name": "Authorization", "scheme": "Bearer" } }]);
+        let request = request(sources, &[("Authorization", b"Bearer header")], &[("token", "query")]);
+        assert_eq!(extract(&request).ok().flatten().as_deref(), Some("query"));
+    }
+}
@@ -0,0 +1,314 @@
+use std::{collections::HashMap, path::PathBuf, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use axum::http::{HeaderMap, HeaderValue};
+use color_eyre::{Result, eyre::eyre};
+use reqwest::{StatusCode, header::RETRY_AFTER};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use super::{request::ProxyRequest, response::ProxyResponse, guard::{claims::Claims, token}};
+
+/// Máximo de chaves acompanhadas por limitador. Ao chegar nele, as inativas são descartadas e,
+/// se ainda faltar espaço, as mais antigas, até sobrar `RETAINED_KEYS`.
+const MAX_TRACKED_KEYS: usize = 10_000;
+const RETAINED_KEYS: usize = MAX_TRACKED_KEYS * 9 / 10;
+/// Chave compartilhada pelas requisições sem a chave configurada e sem IP conhecido.
+const UNKNOWN_KEY: &str = "desconhecido";
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Algorithm {
+    #[default]
+    TokenBucket,
+    SlidingWindow
+}
+
+/// O que identifica quem está sendo limitado.
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RateKey {
+    Ip,
+    /// Token enviado pelo cliente, guardado apenas como hash.
+    Token,
+    /// `sub` devolvido pela guarda. É o único avaliado depois da guarda.
+    Subject,
+    Header(String)
+}
+
+#[derive(Clone, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    limit: u32,
+    window_seconds: u64,
+    key: RateKey,
+    #[serde(default)]
+    algorithm: Algorithm
+}
+
+impl RateLimit {
+    /// Com limite zero o balde nunca se enche, e o tempo até a próxima requisição seria infinito.
+    pub(crate) fn check(&self) -> Result<()> {
+        if self.limit == 0 {
+            return Err(eyre!("Limite de requisições precisa ser maior que zero"));
+        }
+        if self.window_seconds == 0 {
+            return Err(eyre!("Janela do limite de requisições precisa ser maior que zero"));
+        }
+        Ok(())
+    }
+
+    pub(crate) fn is_per_subject(&self) -> bool {
+        self.key == RateKey::Subject
+    }
+
+    fn window(&self) -> Duration {
+        Duration::from_secs(self.window_seconds)
+    }
+
+    /// Sem a chave configurada vale o IP; sem IP, as requisições dividem um mesmo contador em vez de escapar do limite.
+    fn key(&self, request: &ProxyRequest, claims: Option<&Claims>) -> String {
+        let value = match &self.key {
+            RateKey::Ip => request.client_ip.map(|ip| ip.to_string()),
+            RateKey::Token => token::extract(request).ok().flatten().map(|token| hex::encode(Sha256::digest(token.as_bytes()))),
+            RateKey::Subject => claims.and_then(|claims| claims.sub.clone()),
+            RateKey::Header(name) => request.headers.get(name.as_str()).and_then(|value| value.to_str().ok()).map(String::from),
+        };
+
+        value.or_else(|| request.client_ip.map(|ip| ip.to_string())).unwrap_or_else(|| UNKNOWN_KEY.to_string())
+    }
+}
+
+/// Resultado de uma verificação, usado para montar os headers `RateLimit-*`.
+#[derive(Clone, Copy)]
+pub(crate) struct Decision {
+    allowed: bool,
+    limit: u32,
+    remaining: u32,
+    reset: Duration
+}
+
+impl Decision {
+    /// Entre duas decisões, os headers refletem a que tem menos requisições restantes.
+    pub(crate) fn most_restrictive(first: Option<Decision>, second: Option<Decision>) -> Option<Decision> {
+        match (first, second) {
+            (Some(first), Some(second)) => Some(if second.remaining < first.remaining { second } else { first }),
+            (first, second) => first.or(second),
+        }
+    }
+
+    pub(crate) fn apply(&self, headers: &mut HeaderMap) {
+        headers.insert("RateLimit-Limit", HeaderValue::from(self.limit));
+        headers.insert("RateLimit-Remaining", HeaderValue::from(self.remaining));
+        headers.insert("RateLimit-Reset", HeaderValue::from(seconds(self.reset)));
+    }
+
+    fn rejection(&self) -> ProxyResponse {
+        let mut response = ProxyResponse::error("Limite de requisições excedido!", 10, StatusCode::TOO_MANY_REQUESTS);
+        self.apply(response.headers_mut());
+        response.headers_mut().insert(RETRY_AFTER, HeaderValue::from(seconds(self.reset)));
+        response
+    }
+}
+
+fn seconds(duration: Duration) -> u64 {
+    duration.as_secs() + u64::from(duration.subsec_nanos() > 0)
+}
+
+enum Counter {
+    Bucket { tokens: f64, updated: Instant },
+    Window { started: Instant, current: u32, previous: u32 }
+}
+
+impl Counter {
+    fn new(limit: &RateLimit, now: Instant) -> Self {
+        match limit.algorithm {
+            Algorithm::TokenBucket => Counter::Bucket { tokens: limit.limit as f64, updated: now },
+            Algorithm::SlidingWindow => Counter::Window { started: now, current: 0, previous: 0 },
+        }
+    }
+
+    fn updated(&self) -> Instant {
+        match self {
+            Counter::Bucket { updated, .. } => *updated,
+            Counter::Window { started, .. } => *started,
+        }
+    }
+
+    fn take(&mut self, limit: &RateLimit, now: Instant) -> Decision {
+        let window = limit.window();
+        match self {
+            Counter::Bucket { tokens, updated } => {
+                let rate = limit.limit as f64 / window.as_secs_f64();
+                *tokens = (*tokens + now.duration_since(*updated).as_secs_f64() * rate).min(limit.limit as f64);
+                *updated = now;
+
+                let allowed = *tokens >= 1.0;
+                if allowed {
+                    *tokens -= 1.0;
+                }
+                let missing = if allowed { limit.limit as f64 - *tokens } else { 1.0 - *tokens };
+                Decision { allowed, limit: limit.limit, remaining: *tokens as u32, reset: Duration::from_secs_f64(missing / rate) }
+            },
+            Counter::Window { started, current, previous } => {
+                let mut elapsed = now.duration_since(*started);
+                if elapsed >= window {
+                    *previous = if elapsed >= window * 2 { 0 } else { *current };
+                    *current = 0;
+                    *started = now - Duration::from_nanos((elapsed.as_nanos() % window.as_nanos()) as u64);
+                    elapsed = now.duration_since(*started);
+                }
+
+                let weight = 1.0 - elapsed.as_secs_f64() / window.as_secs_f64();
+                let estimate = *previous as f64 * weight + *current as f64;
+                let allowed = estimate + 1.0 <= limit.limit as f64;
+                if allowed {
+                    *current += 1;
+                }
+                let used = (estimate + f64::from(u8::from(allowed))).ceil() as u32;
+                Decision { allowed, limit: limit.limit, remaining: limit.limit.saturating_sub(used), reset: window - elapsed }
+            },
+        }
+    }
+}
+
+pub(crate) struct RateLimiter {
+    limit: RateLimit,
+    counters: Mutex<HashMap<String, Counter>>
+}
+
+impl RateLimiter {
+    pub(crate) fn new(limit: RateLimit) -> Self {
+        RateLimiter { limit, counters: Mutex::new(HashMap::new()) }
+    }
+
+    fn check(&self, key: String) -> Decision {
+        let now = Instant::now();
+        let mut counters = self.counters.lock().expect("Contadores de limite envenenados");
+        if counters.len() >= MAX_TRACKED_KEYS && !counters.contains_key(&key) {
+            self.evict(&mut counters, now);
+        }
+
+        counters.entry(key).or_insert_with(|| Counter::new(&self.limit, now)).take(&self.limit, now)
+    }
+
+    /// Como sobra folga depois do descarte, o custo se dilui entre as próximas chaves novas.
+    fn evict(&self, counters: &mut HashMap<String, Counter>, now: Instant) {
+        let window = self.limit.window() * 2;
+        counters.retain(|_, counter| now.duration_since(counter.updated()) < window);
+        if counters.len() <= RETAINED_KEYS {
+            return;
+        }
+
+        let mut oldest = counters.iter().map(|(key, counter)| (counter.updated(), key.clone())).collect::<Vec<(Instant, String)>>();
+        oldest.sort_unstable();
+        for (_, key) in oldest.into_iter().take(counters.len() - RETAINED_KEYS) {
+            counters.remove(&key);
+        }
+        warn!(keys = MAX_TRACKED_KEYS, "Limite de chaves acompanhadas atingido, contadores mais antigos descartados");
+    }
+}
+
+/// Limites de uma aplicação e de suas rotas.
+#[derive(Clone, Default)]
+pub(crate) struct RateLimiters {
+    application: Option<Arc<RateLimiter>>,
+    routes: HashMap<PathBuf, Arc<RateLimiter>>
+}
+
+impl RateLimiters {
+    pub(crate) fn new(application: Option<RateLimit>, routes: HashMap<PathBuf, RateLimit>) -> Self {
+        RateLimiters {
+            application: application.map(|limit| Arc::new(RateLimiter::new(limit))),
+            routes: routes.into_iter().map(|(route, limit)| (route, Arc::new(RateLimiter::new(limit)))).collect()
+        }
+    }
+
+    /// Troca cada limitador pelo da versão anterior quando o limite é o mesmo, mantendo os contadores.
+    pub(crate) fn inherit(&mut self, previous: &RateLimiters) {
+        if let (Some(current), Some(previous)) = (&mut self.application, &previous.application) {
+            if current.limit == previous.limit {
+                *current = previous.clone();
+            }
+        }
+        for (route, current) in &mut self.routes {
+            if let Some(previous) = previous.routes.get(route).filter(|previous| previous.limit == current.limit) {
+                *current = previous.clone();
+            }
+        }
+    }
+
+    /// Antes da guarda valem os limites por IP, token e header; com `after_guard`, os limites por `sub`.
+    pub(crate) fn check(&self, request: &ProxyRequest, claims: Option<&Claims>, after_guard: bool) -> Result<Option<Decision>, ProxyResponse> {
+        let limiters = self.application.iter().chain(self.routes.get(&request.path))
+            .filter(|limiter| matches!(limiter.limit.key, RateKey::Subject) == after_guard);
+
+        let mut decision = None;
+        for limiter in limiters {
+            let current = limiter.check(limiter.limit.key(request, claims));
+            if !current.allowed {
+                warn!(application = request.application.domain(), path = request.path.to_str(), ip = request.client_ip.map(|ip| ip.to_string()), "Limite de requisições excedido");
+                return Err(current.rejection());
+            }
+            decision = Decision::most_restrictive(decision, Some(current));
+        }
+
+        Ok(decision)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limit(limit: u32) -> RateLimit {
+        RateLimit { limit, window_seconds: 60, key: RateKey::Ip, algorithm: Algorithm::TokenBucket }
+    }
+
+    fn exhaust(limiters: &RateLimiters) {
+        let limiter = limiters.application.as_ref().unwrap();
+        while limiter.check("ip".to_string()).allowed {}
+    }
+
+    #[test]
+    fn unchanged_limit_keeps_its_counters() {
+        let previous = RateLimiters::new(Some(limit(2)), HashMap::new());
+        exhaust(&previous);
+
+        let mut current = RateLimiters::new(Some(limit(2)), HashMap::new());
+        current.inherit(&previous);
+        assert!(!current.application.unwrap().check("ip".to_string()).allowed);
+    }
+
+    #[test]
+    fn changed_limit_starts_over() {
+        let previous = RateLimiters::new(Some(limit(2)), HashMap::new());
+        exhaust(&previous);
+
+        let mut current = RateLimiters::new(Some(limit(5)), HashMap::new());
+        current.inherit(&previous);
+        assert!(current.application.unwrap().check("ip".to_string()).allowed);
+    }
+
+    #[test]
+    fn tracked_keys_are_capped() {
+        let limiter = RateLimiter::new(limit(1));
+        for key in 0..MAX_TRACKED_KEYS {
+            limiter.check(key.to_string());
+        }
+        assert_eq!(limiter.counters.lock().unwrap().len(), MAX_TRACKED_KEYS);
+
+        assert!(!limiter.check((MAX_TRACKED_KEYS - 1).to_string()).allowed);
+        limiter.check("nova".to_string());
+        let counters = limiter.counters.lock().unwrap();
+        assert_eq!(counters.len(), RETAINED_KEYS + 1);
+        assert!(counters.contains_key("nova") && !counters.contains_key("0"));
+    }
+
+    #[test]
+    fn zero_limit_or_window_is_rejected() {
+        assert!(limit(0).check().is_err());
+        assert!(limit(1).check().is_ok());
+        assert!(RateLimit { window_seconds: 0, ..limit(1) }.check().is_err());
+    }
+}
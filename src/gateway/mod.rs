@@ -1,23 +1,189 @@
-use std::{path::PathBuf, collections::HashMap};
-use axum::extract::Query;
+use std::{path::PathBuf, collections::HashMap, sync::Arc};
+use axum::{extract::Query, http::{HeaderMap, header::ORIGIN}};
 use color_eyre::{Result, eyre::eyre};
-use reqwest::{Response, Method};
+use ipnet::IpNet;
+use reqwest::{Response, Method, StatusCode};
+use tracing::warn;
 
-use self::{response::ProxyResponse, request::ProxyRequest, guard::Guardian};
+use crate::{applications::{Application, Enforcement}, server::connection};
+
+use self::{filter::{Inspection, Outcome}, limit::{RateLimiters, Decision}, bulkhead::Bulkhead, response::ProxyResponse, request::ProxyRequest, guard::{Guard, GuardChain, Verdict, unavailable_response, claims}};
 
 pub mod request;
 pub mod response;
 pub(crate) mod guard;
+pub(crate) mod access;
+pub(crate) mod cors;
+pub(crate) mod limit;
+pub(crate) mod bulkhead;
+pub(crate) mod size;
+pub(crate) mod pattern;
+pub(crate) mod filter;
+
+/// Headers que só o gateway escreve para o upstream: claims da guarda, certificado do cliente e marcas das regras.
+const GATEWAY_HEADERS: [&str; 7] = [
+    claims::SUBJECT_HEADER, claims::SCOPE_HEADER, claims::CLIENT_ID_HEADER,
+    connection::SUBJECT_HEADER, connection::SAN_HEADER, connection::FINGERPRINT_HEADER,
+    filter::TAGS_HEADER
+];
+
+/// Descarta as cópias dos headers do gateway enviadas pelo cliente, para que o upstream só receba os gerados aqui.
+pub(crate) fn strip_gateway_headers(headers: &mut HeaderMap) {
+    for header in GATEWAY_HEADERS {
+        headers.remove(header);
+    }
+}
+
+/// Caminhos com `.` ou `..` são recusados: o upstream os resolveria para uma rota diferente da verificada.
+pub(crate) fn invalid_path_response() -> ProxyResponse {
+    ProxyResponse::error("Caminho invalido!", 22, StatusCode::BAD_REQUEST)
+}
+
+/// Uma aplicação e tudo que foi resolvido para atendê-la em tempo de execução.
+#[derive(Clone)]
+pub(crate) struct Gate {
+    pub application: Application,
+    pub guard: GuardChain,
+    pub route_guards: HashMap<PathBuf, GuardChain>,
+    pub trusted_proxies: Arc<Vec<IpNet>>,
+    pub rate_limiters: RateLimiters,
+    pub bulkhead: Option<Arc<Bulkhead>>
+}
+
+/// Limites de uma aplicação, que acumulam estado entre as requisições.
+#[derive(Clone)]
+pub(crate) struct Limits {
+    pub rate_limiters: RateLimiters,
+    pub bulkhead: Option<Arc<Bulkhead>>
+}
+
+impl Limits {
+    pub(crate) fn new(application: &Application) -> Self {
+        Limits {
+            rate_limiters: RateLimiters::new(application.rate_limit().cloned(), application.route_rate_limits().clone()),
+            bulkhead: application.concurrency().cloned().map(|concurrency| Arc::new(Bulkhead::new(concurrency)))
+        }
+    }
+
+    /// Reaproveita os limites da versão anterior que não mudaram, para que um recarregamento não zere
+    /// os contadores nem esqueça as requisições em andamento. Limites alterados começam do zero.
+    pub(crate) fn inherit(&mut self, previous: &Limits) {
+        self.rate_limiters.inherit(&previous.rate_limiters);
+        if let (Some(current), Some(previous)) = (&mut self.bulkhead, &previous.bulkhead) {
+            if current.settings() == previous.settings() {
+                *current = previous.clone();
+            }
+        }
+    }
+}
+
+impl Gate {
+    /// Guardas de rota sempre valem, mesmo em rotas não autenticadas da aplicação.
+    pub(crate) fn guard_for(&self, request: &ProxyRequest) -> Option<&GuardChain> {
+        match self.route_guards.get(&request.path) {
+            Some(guard) => Some(guard),
+            None if request.should_guard() => Some(&self.guard),
+            None => None,
+        }
+    }
+}
+
+/// Verificações que não dependem do corpo: regras, lista de acesso e limites avaliados antes da guarda.
+///
+/// Rodam antes da leitura do corpo, para que um cliente recusado não faça o gateway receber o upload inteiro.
+/// Preflights respondidos pela politica de CORS não consomem os limites.
+pub(crate) fn admit(request: &mut ProxyRequest, gate: &Gate) -> Result<Option<Decision>, ProxyResponse> {
+    let inspection = Inspection { method: &request.method, path: request.path.to_str().unwrap_or_default(), headers: &request.headers, query: &request.query.0 };
+    match filter::evaluate(request.application.rules(), &inspection, &request.application.domain()) {
+        Outcome::Pass(tags) => filter::tag(&mut request.headers, &tags),
+        Outcome::Deny(response) => return Err(response),
+    }
 
-pub async fn route_to(endpoint: &str, request: ProxyRequest, guardian: Guardian) -> Result<ProxyResponse> {
-    if request.should_guard() {
-        let token = request.headers.get("Authorization").map(|h| h.to_str().expect("Authorization ser um UTF-8 valido"));
-        if let Some(response) = guardian.guard(token).await {
-            return Ok(response);
+    access::check(request)?;
+
+    if request.application.cors().is_some() && cors::is_preflight(request) {
+        return Ok(None);
+    }
+    gate.rate_limiters.check(request, None, false)
+}
+
+/// Atende a requisição já admitida por `admit`, com a decisão dos limites avaliados lá.
+pub(crate) async fn route_to(endpoint: &str, request: ProxyRequest, gate: &Gate, quota: Option<Decision>) -> Result<ProxyResponse> {
+    let policy = match request.application.cors() {
+        Some(policy) => policy.clone(),
+        None => return forward(endpoint, request, gate, quota).await,
+    };
+
+    if cors::is_preflight(&request) {
+        return Ok(policy.preflight(&request));
+    }
+
+    let origin = request.headers.get(ORIGIN).and_then(|origin| origin.to_str().ok()).map(String::from);
+    let mut response = forward(endpoint, request, gate, quota).await?;
+    if let Some(origin) = origin {
+        policy.apply(&origin, response.headers_mut());
+    }
+    Ok(response)
+}
+
+async fn forward(endpoint: &str, mut request: ProxyRequest, gate: &Gate, quota: Option<Decision>) -> Result<ProxyResponse> {
+    if let Some(certificate) = request.connection.client_certificate.clone() {
+        certificate.apply(&mut request.headers);
+    }
+
+    let mut claims = None;
+    let enforcement = request.application.enforcement();
+    if let Some(guard) = gate.guard_for(&request).filter(|_| enforcement != Enforcement::Off) {
+        match guard.guard(&request).await {
+            Verdict::Denied(response) if enforcement == Enforcement::Observe => observe(&request, guard, response.status()),
+            Verdict::Denied(response) => return Ok(response),
+            Verdict::Granted(granted) => claims = granted,
+            Verdict::Unavailable if request.application.is_fail_open(&request.path) => {
+                warn!(application = request.application.domain(), path = request.path.to_str(), "Guardião indisponível, liberando rota de baixo risco");
+            },
+            Verdict::Unavailable if enforcement == Enforcement::Observe => observe(&request, guard, unavailable_response().status()),
+            Verdict::Unavailable => return Ok(unavailable_response()),
         };
     };
 
-    route(to_url(endpoint, request.path.clone())?, request).await
+    let quota = match gate.rate_limiters.check(&request, claims.as_ref(), true) {
+        Ok(subject_quota) => Decision::most_restrictive(quota, subject_quota),
+        Err(response) => return Ok(response),
+    };
+
+    if let Some(claims) = claims {
+        claims.apply(&mut request.headers);
+    }
+
+    let _permit = match &gate.bulkhead {
+        Some(bulkhead) => match bulkhead.acquire(&request.application.domain()).await {
+            Ok(permit) => Some(permit),
+            Err(response) => return Ok(response),
+        },
+        None => None,
+    };
+
+    let mut response = route(to_url(endpoint, request.path.clone())?, request).await?;
+    if let Some(quota) = quota {
+        quota.apply(response.headers_mut());
+    }
+    Ok(response)
+}
+
+/// Negação que seria aplicada, registrada com o contexto completo no modo `observe`.
+///
+/// A URI vai sem a query, onde pode estar o token quando a aplicação o lê de um parâmetro.
+fn observe(request: &ProxyRequest, guard: &GuardChain, status: StatusCode) {
+    warn!(
+        application = request.application.domain(),
+        guard = guard.name(),
+        method = request.method.as_str(),
+        path = request.path.to_str(),
+        uri = request.uri.path(),
+        ip = request.client_ip.map(|ip| ip.to_string()),
+        status_code = status.as_u16(),
+        "Negação observada, requisição repassada"
+    );
 }
 
 pub async fn route(url: String, request: ProxyRequest) -> Result<ProxyResponse> {
@@ -0,0 +1,71 @@
+/// Compara o valor com o padrão, onde `*` casa com qualquer trecho.
+pub(crate) fn matches(pattern: &str, value: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    let mut rest = match value.strip_prefix(first) {
+        Some(rest) => rest,
+        None => return false,
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    for (index, part) in parts.iter().enumerate() {
+        if index == parts.len() - 1 {
+            return rest.ends_with(part);
+        }
+        match rest.find(part) {
+            Some(position) => rest = &rest[position + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.is_empty()
+}
+
+/// Se o caminho tem segmentos `.` ou `..`, inclusive na forma `%2e`, que o upstream resolveria
+/// para outra rota. A barra invertida também separa segmentos em urls http.
+pub(crate) fn has_dot_segments(path: &str) -> bool {
+    path.split(['/', '\\']).any(|segment| {
+        let segment = segment.to_ascii_lowercase().replace("%2e", ".");
+        segment == "." || segment == ".."
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{matches, has_dot_segments};
+
+    #[test]
+    fn without_wildcard_matches_exactly() {
+        assert!(matches("/admin", "/admin"));
+        assert!(!matches("/admin", "/admin/users"));
+        assert!(!matches("/admin", "/adm"));
+    }
+
+    #[test]
+    fn wildcard_matches_any_segment() {
+        assert!(matches("*", ""));
+        assert!(matches("/app/admin*", "/app/admin"));
+        assert!(matches("/app/admin*", "/app/admin/users"));
+        assert!(matches("*.php", "/index.php"));
+        assert!(matches("/api/*/export", "/api/v1/users/export"));
+        assert!(!matches("/api/*/export", "/api/v1/import"));
+    }
+
+    #[test]
+    fn parts_do_not_overlap() {
+        assert!(!matches("a*a", "a"));
+        assert!(matches("a*a", "aa"));
+        assert!(!matches("a*b*bc", "abc"));
+        assert!(matches("a*b*bc", "abbc"));
+    }
+
+    #[test]
+    fn dot_segments_are_detected_in_any_form() {
+        for path in ["/x/../admin", "/x/./admin", "..", "/x/%2e%2e/admin", "/x/.%2E/admin", "/x\\..\\admin", "/x/%2e"] {
+            assert!(has_dot_segments(path), "{path}");
+        }
+        for path in ["/admin", "/x/...", "/x/..a/admin", "/file.txt", "/.well-known/x", ""] {
+            assert!(!has_dot_segments(path), "{path}");
+        }
+    }
+}
@@ -1,17 +1,17 @@
 //endpoint: &str, path: PathBuf, method: Method, headers: HeaderMap
 
-use std::{path::PathBuf, collections::HashMap};
+use std::{path::PathBuf, collections::HashMap, net::IpAddr};
 
-use axum::{http::HeaderMap, body::Bytes, extract::Query, http::StatusCode};
+use axum::{http::{HeaderMap, Uri}, body::Bytes, extract::Query, http::StatusCode};
 
 use axum::{
     async_trait,
     extract::{FromRequest, RequestParts},
 };
 
-use crate::applications::Application;
+use crate::{applications::Application, server::connection::Connection};
 
-use super::Method;
+use super::{Method, cors};
 
 #[derive(Clone)]
 pub struct ProxyRequest{
@@ -20,12 +20,15 @@ pub struct ProxyRequest{
     pub headers: HeaderMap,
     pub body: Bytes,
     pub query: Query<HashMap<String, String>>,
+    pub uri: Uri,
+    pub connection: Connection,
+    pub client_ip: Option<IpAddr>,
     pub application: Application
 }
 
 impl ProxyRequest {
     pub fn should_guard(&self)-> bool {
-        if Method::OPTIONS == self.method {
+        if cors::is_preflight(self) {
             return false
         }
 
@@ -48,4 +51,17 @@ where B: Send,
     async fn from_request(request: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
        Ok(ExtractMethod(request.method().clone()))
     }
+}
+
+pub struct ExtractConnection(pub(crate) Connection);
+
+#[async_trait]
+impl<B> FromRequest<B> for ExtractConnection
+where B: Send,
+{
+    type Rejection = (StatusCode, &'static str);
+
+    async fn from_request(request: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+       Ok(ExtractConnection(request.extensions().get::<Connection>().cloned().unwrap_or_default()))
+    }
 }
\ No newline at end of file
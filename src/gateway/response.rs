@@ -2,7 +2,17 @@ use axum::{
     http::{StatusCode, HeaderMap, HeaderValue},
     response::{IntoResponse}, body::Bytes
 };
-use reqwest::header::{CONTENT_LENGTH};
+use reqwest::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use serde::Serialize;
+
+use crate::date::DateTime;
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    message: &'a str,
+    code: u8,
+    timestamp: DateTime
+}
 
 pub struct ProxyResponse {
     body: Bytes,
@@ -15,6 +25,22 @@ impl ProxyResponse {
         Self::proxy(Bytes::from(body), status, headers)
     }
 
+    /// Resposta de erro gerada pelo próprio gateway, no mesmo formato JSON para todos os casos.
+    pub fn error(message: &str, code: u8, status: StatusCode) -> Self {
+        let body = ErrorBody { message, code, timestamp: DateTime::now() };
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        Self::new(serde_json::to_string(&body).expect("Fixed message"), status, headers)
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn headers_mut(&mut self) -> &mut HeaderMap {
+        &mut self.headers
+    }
+
     fn headers(body: &Bytes, proxy_headers: HeaderMap) -> HeaderMap {
         let mut headers = HeaderMap::new();
         headers.extend(proxy_headers);
@@ -0,0 +1,98 @@
+use std::time::Duration;
+
+use axum::{body::{Body, Bytes}, http::{HeaderMap, Uri}};
+use hyper::body::HttpBody;
+use reqwest::{StatusCode, header::CONTENT_LENGTH};
+use serde::Deserialize;
+use tracing::warn;
+
+use crate::applications::Application;
+
+use super::response::ProxyResponse;
+
+/// Corpo máximo quando a aplicação não define o seu, já que o corpo inteiro fica em memória.
+const DEFAULT_MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+
+/// Limites de tamanho das requisições de uma aplicação. Os campos de URI e headers ausentes não são verificados.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SizeLimits {
+    #[serde(default = "default_max_body_bytes")]
+    max_body_bytes: usize,
+    max_headers: Option<usize>,
+    max_header_bytes: Option<usize>,
+    max_uri_length: Option<usize>
+}
+
+fn default_max_body_bytes() -> usize {
+    DEFAULT_MAX_BODY_BYTES
+}
+
+impl Default for SizeLimits {
+    fn default() -> Self {
+        SizeLimits { max_body_bytes: DEFAULT_MAX_BODY_BYTES, max_headers: None, max_header_bytes: None, max_uri_length: None }
+    }
+}
+
+/// Verifica URI e headers, antes de qualquer leitura do corpo.
+pub(crate) fn check_head(application: &Application, uri: &Uri, headers: &HeaderMap) -> Result<(), ProxyResponse> {
+    let limits = application.size_limits();
+
+    let uri_length = uri.to_string().len();
+    if limits.max_uri_length.map(|max| uri_length > max).unwrap_or(false) {
+        warn!(application = application.domain(), uri_length, "URI acima do limite");
+        return Err(ProxyResponse::error("URI muito longa!", 14, StatusCode::URI_TOO_LONG));
+    }
+
+    let header_bytes: usize = headers.iter().map(|(name, value)| name.as_str().len() + value.len()).sum();
+    let too_many = limits.max_headers.map(|max| headers.len() > max).unwrap_or(false);
+    let too_large = limits.max_header_bytes.map(|max| header_bytes > max).unwrap_or(false);
+    if too_many || too_large {
+        warn!(application = application.domain(), headers = headers.len(), header_bytes, "Headers acima do limite");
+        return Err(ProxyResponse::error("Headers muito grandes!", 13, StatusCode::REQUEST_HEADER_FIELDS_TOO_LARGE));
+    }
+
+    Ok(())
+}
+
+/// Lê o corpo respeitando o limite da aplicação, ou o padrão, e o tempo máximo de leitura do listener.
+///
+/// Um `Content-Length` acima do limite é recusado sem ler nada.
+pub(crate) async fn read_body(application: &Application, headers: &HeaderMap, body: Body, timeout: Option<Duration>) -> Result<Bytes, ProxyResponse> {
+    let max = application.size_limits().max_body_bytes;
+    let declared = headers.get(CONTENT_LENGTH).and_then(|value| value.to_str().ok()).and_then(|value| value.parse::<usize>().ok());
+    if let Some(declared) = declared.filter(|declared| *declared > max) {
+        return Err(too_large(application, declared));
+    }
+
+    let read = collect(application, body, max);
+    match timeout {
+        Some(timeout) => tokio::time::timeout(timeout, read).await.unwrap_or_else(|_| {
+            warn!(application = application.domain(), "Tempo de leitura do corpo esgotado");
+            Err(ProxyResponse::error("Tempo de envio da requisição esgotado!", 15, StatusCode::REQUEST_TIMEOUT))
+        }),
+        None => read.await,
+    }
+}
+
+async fn collect(application: &Application, mut body: Body, max: usize) -> Result<Bytes, ProxyResponse> {
+    let mut buffer = Vec::new();
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|error| {
+            warn!(exception = format!("{:?}", error), "Não foi possivel ler o corpo da requisição");
+            ProxyResponse::error("Corpo da requisição invalido!", 16, StatusCode::BAD_REQUEST)
+        })?;
+
+        buffer.extend_from_slice(&chunk);
+        if buffer.len() > max {
+            return Err(too_large(application, buffer.len()));
+        }
+    }
+
+    Ok(Bytes::from(buffer))
+}
+
+fn too_large(application: &Application, size: usize) -> ProxyResponse {
+    warn!(application = application.domain(), size, "Corpo acima do limite");
+    ProxyResponse::error("Corpo da requisição muito grande!", 12, StatusCode::PAYLOAD_TOO_LARGE)
+}
@@ -0,0 +1,217 @@
+use std::{collections::BTreeMap, path::PathBuf, sync::{Arc, Mutex}, time::{Duration, Instant}};
+
+use axum::{Json, Extension, http::StatusCode};
+use serde::Serialize;
+use serde_json::{Value, json};
+use tracing::{info, warn};
+
+use crate::{config::HealthSettings, date::DateTime, gateway, management::State, reload::Runtime, shutdown::Shutdown};
+
+const DEFAULT_HEALTH_PATH: &str = "/";
+
+#[derive(Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Status {
+    Up,
+    Down,
+    /// Ainda não verificado desde a subida ou a inclusão da aplicação.
+    Unknown
+}
+
+/// Resultado da última verificação de um upstream ou do Guardião.
+///
+/// A url e o erro só vão para os logs: o `/health` é público e não deve revelar hosts internos.
+#[derive(Clone, Serialize)]
+pub(crate) struct Check {
+    status: Status,
+    #[serde(skip)]
+    url: String,
+    status_code: Option<u16>,
+    latency_ms: Option<u64>,
+    last_check: Option<DateTime>,
+    #[serde(skip)]
+    error: Option<String>
+}
+
+impl Check {
+    fn unknown(url: String) -> Self {
+        Check { status: Status::Unknown, url, status_code: None, latency_ms: None, last_check: None, error: None }
+    }
+}
+
+/// Verificações periódicas dos upstreams e do Guardião, consultadas pelos endpoints de health.
+pub(crate) struct Health {
+    started: DateTime,
+    uptime: Instant,
+    client: reqwest::Client,
+    guardian: Mutex<Option<Check>>,
+    applications: Mutex<BTreeMap<String, Check>>
+}
+
+impl Health {
+    pub(crate) fn new(settings: HealthSettings) -> Arc<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(settings.timeout_ms))
+            .build()
+            .expect("Cliente HTTP das verificações de health");
+
+        Arc::new(Health {
+            started: DateTime::now(),
+            uptime: Instant::now(),
+            client,
+            guardian: Mutex::new(None),
+            applications: Mutex::new(BTreeMap::new())
+        })
+    }
+
+    fn guardian(&self) -> Option<Check> {
+        self.guardian.lock().expect("Verificações envenenadas").clone()
+    }
+
+    fn application(&self, name: &str) -> Option<Check> {
+        self.applications.lock().expect("Verificações envenenadas").get(name).cloned()
+    }
+
+    /// Um upstream está de pé quando responde sem erro 5xx; qualquer resposta do Guardião basta.
+    async fn probe(&self, url: String, accept_any: bool) -> Check {
+        let started = Instant::now();
+        let response = self.client.get(&url).send().await;
+        let latency_ms = Some(started.elapsed().as_millis() as u64);
+        match response {
+            Ok(response) => Check {
+                status: if accept_any || !response.status().is_server_error() { Status::Up } else { Status::Down },
+                url,
+                status_code: Some(response.status().as_u16()),
+                latency_ms,
+                last_check: Some(DateTime::now()),
+                error: None
+            },
+            Err(error) => Check { status: Status::Down, url, status_code: None, latency_ms, last_check: Some(DateTime::now()), error: Some(error.to_string()) },
+        }
+    }
+
+    async fn check(self: &Arc<Self>, state: &State) {
+        let probes = state.apps().iter()
+            .filter_map(|application| {
+                let url = gateway::to_url(application.endpoint(), PathBuf::from(application.health_path().unwrap_or(DEFAULT_HEALTH_PATH))).ok()?;
+                let health = self.clone();
+                Some((application.domain(), tokio::spawn(async move { health.probe(url, false).await })))
+            })
+            .collect::<Vec<_>>();
+        let guardian = state.guardian_url().map(|url| {
+            let health = self.clone();
+            let url = url.to_string();
+            tokio::spawn(async move { health.probe(url, true).await })
+        });
+
+        let mut applications = BTreeMap::new();
+        for (name, probe) in probes {
+            if let Ok(check) = probe.await {
+                if check.status == Status::Down && self.application(&name).is_none_or(|previous| previous.status != Status::Down) {
+                    warn!(application = name, url = check.url, error = check.error, status_code = check.status_code, "Upstream indisponivel");
+                }
+                applications.insert(name, check);
+            }
+        }
+        let guardian = match guardian {
+            Some(probe) => probe.await.ok(),
+            None => None,
+        };
+        let was_down = self.guardian().is_some_and(|previous| previous.status == Status::Down);
+        if let Some(check) = guardian.as_ref().filter(|check| check.status == Status::Down && !was_down) {
+            warn!(url = check.url, error = check.error, "Guardião inalcançavel");
+        }
+
+        *self.applications.lock().expect("Verificações envenenadas") = applications;
+        *self.guardian.lock().expect("Verificações envenenadas") = guardian;
+    }
+}
+
+/// Verifica os upstreams da versão atual da configuração a cada intervalo, começando na subida.
+pub(crate) fn watch(health: Arc<Health>, runtime: Arc<Runtime>, settings: HealthSettings) {
+    info!(interval_ms = settings.interval_ms, "Verificação dos upstreams iniciada");
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_millis(settings.interval_ms));
+        loop {
+            interval.tick().await;
+            health.check(&runtime.state()).await;
+        }
+    });
+}
+
+/// O processo está vivo enquanto responde.
+pub(crate) async fn live() -> &'static str {
+    "live"
+}
+
+/// Pronto para receber tráfego: configuração carregada, Guardião alcançavel quando configurado
+/// e fora do desligamento.
+pub(crate) async fn ready(
+    Extension(state): Extension<Arc<State>>,
+    health: Option<Extension<Arc<Health>>>,
+    shutdown: Option<Extension<Arc<Shutdown>>>
+) -> (StatusCode, Json<Value>) {
+    let draining = shutdown.is_some_and(|Extension(shutdown)| shutdown.is_draining());
+    let guardian = guardian_status(&state, health.as_ref().map(|Extension(health)| health.as_ref()));
+    let ready = !draining && matches!(guardian, None | Some(Status::Up));
+
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(json!({
+        "ready": ready,
+        "configuration": "loaded",
+        "guardian": guardian,
+        "draining": draining
+    })))
+}
+
+/// Situação detalhada do gateway e de cada upstream, para a página de status.
+///
+/// Responde 503 só durante o desligamento; upstreams fora do ar aparecem como `degraded`.
+pub(crate) async fn detailed(
+    Extension(state): Extension<Arc<State>>,
+    health: Option<Extension<Arc<Health>>>,
+    shutdown: Option<Extension<Arc<Shutdown>>>
+) -> (StatusCode, Json<Value>) {
+    let health = health.map(|Extension(health)| health);
+    let draining = shutdown.is_some_and(|Extension(shutdown)| shutdown.is_draining());
+    let applications = state.apps().iter()
+        .map(|application| {
+            let url = gateway::to_url(application.endpoint(), PathBuf::from(application.health_path().unwrap_or(DEFAULT_HEALTH_PATH))).unwrap_or_default();
+            let check = health.as_ref().and_then(|health| health.application(&application.domain())).unwrap_or_else(|| Check::unknown(url));
+            (application.domain(), check)
+        })
+        .collect::<BTreeMap<String, Check>>();
+    let guardian = state.guardian_url().map(|url| health.as_ref().and_then(|health| health.guardian()).unwrap_or_else(|| Check::unknown(url.to_string())));
+
+    let degraded = applications.values().chain(guardian.as_ref()).any(|check| check.status == Status::Down);
+    let status = match (draining, degraded) {
+        (true, _) => "draining",
+        (false, true) => "degraded",
+        (false, false) => "up",
+    };
+    let code = if draining { StatusCode::SERVICE_UNAVAILABLE } else { StatusCode::OK };
+    (code, Json(json!({
+        "status": status,
+        "version": version(),
+        "started_at": health.as_ref().map(|health| health.started.clone()),
+        "uptime_seconds": health.as_ref().map(|health| health.uptime.elapsed().as_secs()),
+        "guardian": guardian,
+        "applications": applications
+    })))
+}
+
+/// Versão e build do gateway, dos metadados do Cargo.
+pub(crate) fn version() -> Value {
+    json!({
+        "name": env!("CARGO_PKG_NAME"),
+        "version": env!("CARGO_PKG_VERSION"),
+        "profile": if cfg!(debug_assertions) { "debug" } else { "release" },
+        "target": format!("{}-{}", std::env::consts::ARCH, std::env::consts::OS)
+    })
+}
+
+/// `None` quando não há Guardião configurado.
+fn guardian_status(state: &State, health: Option<&Health>) -> Option<Status> {
+    state.guardian_url()?;
+    Some(health.and_then(Health::guardian).map_or(Status::Unknown, |check| check.status))
+}
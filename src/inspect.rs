@@ -0,0 +1,101 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use axum::{body::Bytes, extract::Query, http::{HeaderMap, Method, Uri}};
+use color_eyre::{Result, eyre::eyre};
+use reqwest::Url;
+
+use crate::{applications::{Application, Enforcement}, gateway::{self, request::ProxyRequest}, management::State, server::connection::Connection};
+
+const HEADERS: [&str; 7] = ["APLICAÇÃO", "PREFIXO", "UPSTREAM", "GUARDA", "MODO", "LISTENERS", "ROTAS PÚBLICAS"];
+
+/// Tabela das aplicações atendidas, com prefixo, upstream, guarda, listeners e rotas públicas.
+pub fn routes(state: &State) -> Result<String> {
+    let mut rows = vec![HEADERS.iter().map(|header| header.to_string()).collect::<Vec<String>>()];
+    for application in state.apps().iter() {
+        let public = application.unauthenticated_routes().iter().filter_map(|route| route.to_str()).collect::<Vec<&str>>();
+        rows.push(vec![
+            application.domain(),
+            format!("/{}/", application.domain()),
+            application.endpoint().to_string(),
+            state.gate(application)?.guard.name(),
+            enforcement(application).to_string(),
+            if application.listeners().is_empty() { "todos".to_string() } else { application.listeners().join(", ") },
+            if public.is_empty() { "-".to_string() } else { public.join(", ") },
+        ]);
+    }
+
+    let widths = (0..HEADERS.len())
+        .map(|column| rows.iter().map(|row| row[column].chars().count()).max().unwrap_or_default())
+        .collect::<Vec<usize>>();
+    let mut table = rows.iter()
+        .map(|row| row.iter().zip(&widths).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect::<Vec<String>>().join("  ").trim_end().to_string())
+        .collect::<Vec<String>>()
+        .join("\n");
+
+    let disabled = state.catalog().iter().filter(|entry| entry.disabled).map(|entry| entry.name.as_str()).collect::<Vec<&str>>();
+    if !disabled.is_empty() {
+        table.push_str(&format!("\n\nDesativadas: {}", disabled.join(", ")));
+    }
+    Ok(table)
+}
+
+/// Explica como o gateway trataria a requisição: aplicação, guarda e url final no upstream.
+pub fn resolve(state: &State, method: &str, url: &str) -> Result<String> {
+    let method = Method::from_bytes(method.to_uppercase().as_bytes()).map_err(|_| eyre!("Método invalido {}", method))?;
+    let url = match url.starts_with('/') {
+        true => Url::parse("http://localhost")?.join(url)?,
+        false => Url::parse(url)?,
+    };
+
+    let (domain, path) = match url.path().trim_start_matches('/').split_once('/') {
+        Some((domain, path)) => (domain.to_lowercase(), format!("/{}", path)),
+        None => return Ok(format!("Nenhuma aplicação atende {}, o caminho precisa ser /<aplicação>/<rota>", url.path())),
+    };
+    if let Some(admin) = state.admin().filter(|admin| admin.address.is_none() && url.path().starts_with(&admin.prefix)) {
+        return Ok(format!("Rota da API de administração, sob o prefixo {}", admin.prefix));
+    }
+
+    let application = match state.apps().iter().find(|application| application.domain() == domain) {
+        Some(application) => application,
+        None => return Ok(format!("Nenhuma aplicação atende {}", url.path())),
+    };
+
+    let gate = state.gate(application)?;
+    let request = ProxyRequest {
+        path: PathBuf::from(&path),
+        method: method.clone(),
+        headers: HeaderMap::new(),
+        body: Bytes::new(),
+        query: Query(url.query_pairs().into_owned().collect::<HashMap<String, String>>()),
+        uri: url.as_str().parse::<Uri>()?,
+        connection: Connection::default(),
+        client_ip: None,
+        application: application.clone()
+    };
+    let guard = match gate.guard_for(&request) {
+        Some(guard) => guard.name(),
+        None => "nenhuma (rota pública)".to_string(),
+    };
+
+    let mut upstream = gateway::to_url(application.endpoint(), PathBuf::from(&path))?;
+    if let Some(query) = url.query() {
+        upstream = format!("{}?{}", upstream, query);
+    }
+
+    Ok([
+        format!("Aplicação: {}", application.domain()),
+        format!("Rota: {}", path),
+        format!("Guarda: {}", guard),
+        format!("Modo: {}", enforcement(application)),
+        format!("Listeners: {}", if application.listeners().is_empty() { "todos".to_string() } else { application.listeners().join(", ") }),
+        format!("Upstream: {} {}", method, upstream),
+    ].join("\n"))
+}
+
+fn enforcement(application: &Application) -> &'static str {
+    match application.enforcement() {
+        Enforcement::Enforce => "enforce",
+        Enforcement::Observe => "observe",
+        Enforcement::Off => "off",
+    }
+}
@@ -1,9 +1,23 @@
-use std::sync::Arc;
+// Guardas e validações devolvem a resposta de erro pronta como `Err(ProxyResponse)`.
+#![allow(clippy::result_large_err)]
 
-use axum::{Router, routing::get};
+use std::{path::{Path, PathBuf}, sync::Arc};
+
+use axum::{Router, Extension, routing::get, middleware};
 use color_eyre::{Result};
+use tokio::task::JoinSet;
+use tracing::info;
 
+use admin::{Admin, store::Overlay};
+use config::{Config, LoggingSettings, Options};
 use management::{State};
+use reload::{Generation, Runtime};
+use health::Health;
+use shutdown::Shutdown;
+use server::{listener::ListenerSettings, tls};
+use validation::Problems;
+
+pub use secret::redact;
 
 
 mod log;
@@ -12,22 +26,118 @@ mod gateway;
 mod management;
 mod applications;
 mod date;
+mod server;
+mod reload;
+mod validation;
+mod admin;
+mod shutdown;
+mod health;
+mod secret;
+pub mod inspect;
+pub mod config;
+
+/// Com um arquivo de configuração as envs de aplicações ficam de fora; sem ele, valem as envs.
+pub fn install(options: &Options) -> Result<State> {
+    let config = options.config.as_deref().map(config::load).transpose()?;
+    let logging = match &options.log_level {
+        Some(level) => LoggingSettings::new(level.clone()),
+        None => config.as_ref().map(|config| config.logging.clone()).unwrap_or_else(management::env_logging),
+    };
+    log::install(logging.level()?)?;
+
+    let mut state = load(options.config.as_deref(), config, None)?;
+    state.override_listeners(options.listen.clone());
+    Ok(state)
+}
+
+/// Relê a configuração da mesma origem da subida e monta a nova versão do gateway,
+/// herdando da versão anterior o estado dos limites.
+pub(crate) fn build(config_path: Option<&Path>, overlay: Option<&Overlay>, previous: &State) -> Result<Generation> {
+    let mut state = load(config_path, config_path.map(config::load).transpose()?, overlay)?;
+    state.inherit(previous);
+    let state = Arc::new(state);
+    Ok(Generation { state: state.clone(), router: app(state)? })
+}
+
+fn load(config_path: Option<&Path>, config: Option<Config>, overlay: Option<&Overlay>) -> Result<State> {
+    let mut problems = Problems::default();
+    let config = match config {
+        Some(config) => {
+            info!(path = config_path.and_then(|path| path.to_str()), "Configuração lida do arquivo");
+            config
+        },
+        None => management::env_config(&mut problems),
+    };
+    management::install_state(config, overlay, problems)
+}
+
+/// Router de uma versão da configuração, com o `State` dela na camada `Extension`.
+pub fn app(state: Arc<State>) -> Result<Router> {
+    Ok(routes(state.clone())?.layer(Extension(state)))
+}
+
+/// Atende em todos os listeners configurados; o primeiro que falhar encerra o gateway.
+///
+/// O router pode ser trocado por um recarregamento da configuração ou pela API de administração
+/// sem derrubar as conexões. Retorna depois do SIGTERM ou SIGINT, com as conexões drenadas.
+pub async fn serve(app: Router, state: Arc<State>, config_path: Option<PathBuf>) -> Result<()> {
+    let shutdown = Shutdown::new(state.shutdown());
+    shutdown.listen();
+
+    let runtime = Runtime::new(config_path, Generation { state: state.clone(), router: app });
+    reload::watch(runtime.clone());
+    let health = Health::new(state.health());
+    health::watch(health.clone(), runtime.clone(), state.health());
+    let counter = shutdown.clone();
+    let mut app = reload::swappable(runtime.clone())
+        .layer(middleware::from_fn(move |request, next| shutdown::count(counter.clone(), request, next)))
+        .layer(Extension(shutdown.clone()))
+        .layer(Extension(health));
+
+    let acceptor = state.tls().map(tls::shared).transpose()?;
+    let acceptor_for = |listener: &ListenerSettings| acceptor.clone().filter(|_| listener.uses_tls());
+
+    let mut servers = JoinSet::new();
+    if let Some(settings) = state.admin() {
+        let admin = admin::router(Arc::new(Admin::new(settings.clone(), runtime)));
+        match settings.address {
+            Some(address) => {
+                let listener = ListenerSettings { tls: settings.tls, ..ListenerSettings::tcp(address) };
+                servers.spawn(server::serve(admin, listener.clone(), acceptor_for(&listener), state.timeouts(), shutdown.clone()));
+            },
+            None => app = app.nest(&settings.prefix, admin),
+        }
+    }
+
+    for listener in state.listeners().iter().cloned() {
+        let acceptor = acceptor_for(&listener);
+        servers.spawn(server::serve(app.clone(), listener, acceptor, state.timeouts(), shutdown.clone()));
+    }
 
-pub fn install() -> Result<State> {
-    log::install()?;
-    management::install_state()
+    // Os listeners terminam na ordem em que falham ou param; um erro derruba os demais junto com o `JoinSet`.
+    while let Some(server) = servers.join_next().await {
+        server??;
+    }
+    shutdown.drain().await;
+    Ok(())
 }
 
 pub fn routes(state: Arc<State>) -> Result<Router> {
+    let rules = state.rules();
     Ok(state.apps()
     .iter()
-    .map(|app| routing::router(app.clone(), state.guardian().clone()))
+    .map(|app| Ok(routing::router(state.gate(app)?)))
+    .collect::<Result<Vec<Router>>>()?
+    .into_iter()
     .reduce(|router: Router, router_b: Router| router.merge(router_b))
     .unwrap_or_default()
-    .merge(emerald_routes()))
+    .merge(emerald_routes())
+    .layer(middleware::from_fn(move |request, next| gateway::filter::global(rules.clone(), request, next))))
 }
 
 fn emerald_routes() -> Router {
     Router::new()
-     .route("/health", get( || async { "up" }))
+     .route("/health", get(health::detailed))
+     .route("/health/live", get(health::live))
+     .route("/health/ready", get(health::ready))
 }
\ No newline at end of file
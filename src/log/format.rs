@@ -1,4 +1,4 @@
-use crate::date::DateTime;
+use crate::{date::DateTime, secret};
 
 use super::storage::JsonStorage;
 use serde::ser::{SerializeMap, Serializer};
@@ -44,14 +44,14 @@ impl<W: MakeWriter> FormattingLayer<W> {
     /// - a `make_writer`, which will be used to get a `Write` instance to write formatted records to.
     ///
     /// ## Using stdout
-    /// ```rust
+    /// ```ignore
     /// use formatter::FormattingLayer;
     ///
     /// let formatting_layer = FormattingLayer::new("tracing_example".into(), std::io::stdout);
     /// ```
     ///
     /// If you prefer, you can use closure syntax:
-    /// ```rust
+    /// ```ignore
     /// use formatter::FormattingLayer;
     ///
     /// let formatting_layer = FormattingLayer::new("tracing_example".into(), || std::io::stdout());
@@ -141,7 +141,10 @@ impl<W: MakeWriter> FormattingLayer<W> {
     /// If we write directly to the writer returned by self.make_writer in more than one go
     /// we can end up with broken/incoherent bits and pieces of those records when
     /// running multi-threaded/concurrent programs.
-    fn emit(&self, mut buffer: Vec<u8>, meta: &Metadata<'_>) -> Result<(), std::io::Error> {
+    ///
+    /// Known secrets are masked in the whole record, whatever field they ended up in.
+    fn emit(&self, buffer: Vec<u8>, meta: &Metadata<'_>) -> Result<(), std::io::Error> {
+        let mut buffer = secret::redact(&String::from_utf8_lossy(&buffer)).into_bytes();
         buffer.write_all(b"\n")?;
         self.make_writer.make_writer_for(meta).write_all(&buffer)
     }
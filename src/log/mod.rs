@@ -9,8 +9,8 @@ mod storage;
 mod format;
 
 
-pub fn install() -> Result<()>{
-    let writer = std::io::stdout.with_max_level(Level::INFO);
+pub fn install(level: Level) -> Result<()>{
+    let writer = std::io::stdout.with_max_level(level);
     let formatting_layer = FormattingLayer::new("emerald_herald".into(), writer);
     let subscriber = Registry::default()
         .with(JsonStorageLayer)
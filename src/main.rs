@@ -1,23 +1,75 @@
-use axum::{Router, extract::Extension};
-use std::{net::SocketAddr, sync::Arc};
+use std::{net::SocketAddr, path::PathBuf, sync::Arc};
+
+use clap::{Parser, Subcommand};
+use color_eyre::{Result, eyre::WrapErr};
+use emerald_herald::config::Options;
+
+const INSPECTION_LOG_LEVEL: &str = "warn";
+
+/// Gateway de autenticação na frente das aplicações.
+#[derive(Parser)]
+#[command(version)]
+struct Cli {
+    /// Arquivo de configuração em TOML, YAML ou JSON; sem ele, valem as envs.
+    #[arg(long, env = "CONFIG_FILE", global = true)]
+    config: Option<PathBuf>,
+    /// Endereço de escuta no lugar dos listeners da configuração; pode ser repetido.
+    #[arg(long, global = true)]
+    listen: Vec<SocketAddr>,
+    /// Nível de log no lugar do configurado.
+    #[arg(long, global = true)]
+    log_level: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Atende as requisições, o padrão sem subcomando.
+    Serve,
+    /// Valida a configuração e sai com erro se houver problemas.
+    CheckConfig,
+    /// Lista as aplicações, prefixos e rotas públicas.
+    Routes,
+    /// Mostra a aplicação, a guarda e a url final de uma requisição.
+    Resolve {
+        method: String,
+        url: String
+    }
+}
 
 #[tokio::main]
 async fn main() {
-    serve().await
+    color_eyre::install().expect("Não foi possivel instalar color eyre!");
+    if let Err(error) = run(Cli::parse()).await {
+        eprintln!("{}", emerald_herald::redact(&format!("{:?}", error)));
+        std::process::exit(1);
+    }
 }
 
-async fn serve() {
-    color_eyre::install().expect("Não foi possivel instalar color eyre!");
-    let state = Arc::new(emerald_herald::install().expect("Não foi possivel instalar configurações!"));
-    let routes = emerald_herald::routes(state.clone()).expect("Não foi possivel criar rotas!");
-    let app = Router::new().merge(routes).layer(Extension(state));
-
-    let addr = SocketAddr::from(([0, 0, 0, 0], 8080));
-    
-    tracing::info!("listening on {}", addr);
-    
-    axum::Server::bind(&addr)
-        .serve(app.into_make_service())
-        .await
-        .unwrap();
+async fn run(cli: Cli) -> Result<()> {
+    let command = cli.command.unwrap_or(Command::Serve);
+    let mut options = Options { config: cli.config, listen: cli.listen, log_level: cli.log_level };
+    if !matches!(command, Command::Serve) {
+        options.log_level = options.log_level.or_else(|| Some(INSPECTION_LOG_LEVEL.to_string()));
+    }
+
+    let state = Arc::new(emerald_herald::install(&options).wrap_err("Não foi possivel instalar configurações!")?);
+    let app = emerald_herald::app(state.clone()).wrap_err("Não foi possivel criar rotas!")?;
+
+    match command {
+        Command::Serve => emerald_herald::serve(app, state, options.config).await,
+        Command::CheckConfig => {
+            println!("Configuração valida, {} aplicação(ões)", state.apps().iter().count());
+            Ok(())
+        },
+        Command::Routes => {
+            println!("{}", emerald_herald::redact(&emerald_herald::inspect::routes(&state)?));
+            Ok(())
+        },
+        Command::Resolve { method, url } => {
+            println!("{}", emerald_herald::redact(&emerald_herald::inspect::resolve(&state, &method, &url)?));
+            Ok(())
+        },
+    }
 }
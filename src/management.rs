@@ -1,16 +1,60 @@
-use std::{env, collections::HashMap};
+use std::{env, collections::HashMap, str::FromStr, sync::Arc, net::SocketAddr, path::PathBuf, time::Duration};
 
-use crate::{applications::{Applications, Application}, gateway::guard::Guardian};
+use crate::{config::{Config, TimeoutSettings, ShutdownSettings, HealthSettings, LoggingSettings, default_header_read_ms, default_body_read_ms, default_drain_timeout_ms, default_health_interval_ms, default_health_timeout_ms, default_reload_interval_seconds}, applications::{Applications, Application}, gateway::{Gate, Limits, filter::Rule, guard::{Guard, GuardChain, GuardDefinition, NoGuard, guardian::{Guardian, GuardianDefinition, Introspection, ForwardAuth, default_timeout_ms, default_retries, default_cache_ttl_seconds}, GUARDIAN, NONE}}, server::{Timeouts, listener::ListenerSettings, tls::TlsSettings}, validation::{self, Problems}, secret, date::DateTime, admin::{AdminSettings, store::{Entry, Overlay}}};
 
-use color_eyre::{Result};
-use tracing::warn;
+use color_eyre::{Result, eyre::eyre};
+use ipnet::IpNet;
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
 
 const APPLICATION_MAP_KEY: &str = "APPLICATIONS";
 const GUARDIAN_URL_KEY: &str = "GUARDIAN_URL";
+const GUARDS_KEY: &str = "GUARDS";
+const TLS_KEY: &str = "TLS";
+const RULES_KEY: &str = "RULES";
+const ADMIN_KEY: &str = "ADMIN";
+const LISTENERS_KEY: &str = "LISTENERS";
+const TRUSTED_PROXIES_KEY: &str = "TRUSTED_PROXIES";
+const HEADER_READ_TIMEOUT_KEY: &str = "HEADER_READ_TIMEOUT_MS";
+const BODY_READ_TIMEOUT_KEY: &str = "BODY_READ_TIMEOUT_MS";
+const LOG_LEVEL_KEY: &str = "LOG_LEVEL";
+const UNREADY_DELAY_KEY: &str = "UNREADY_DELAY_MS";
+const DRAIN_TIMEOUT_KEY: &str = "DRAIN_TIMEOUT_MS";
+const HEALTH_INTERVAL_KEY: &str = "HEALTH_INTERVAL_MS";
+const HEALTH_TIMEOUT_KEY: &str = "HEALTH_TIMEOUT_MS";
+const GUARDIAN_CLIENT_ID_KEY: &str = "GUARDIAN_CLIENT_ID";
+const GUARDIAN_CLIENT_SECRET_KEY: &str = "GUARDIAN_CLIENT_SECRET";
+const GUARDIAN_FORWARD_HEADERS_KEY: &str = "GUARDIAN_FORWARD_HEADERS";
+const GUARDIAN_TIMEOUT_KEY: &str = "GUARDIAN_TIMEOUT_MS";
+const GUARDIAN_RETRIES_KEY: &str = "GUARDIAN_RETRIES";
+const GUARDIAN_CACHE_TTL_KEY: &str = "GUARDIAN_CACHE_TTL_SECONDS";
+const CONFIG_KEYS: [&str; 21] = [
+    APPLICATION_MAP_KEY, GUARDIAN_URL_KEY, GUARDS_KEY, TLS_KEY, RULES_KEY, ADMIN_KEY, LISTENERS_KEY, TRUSTED_PROXIES_KEY,
+    HEADER_READ_TIMEOUT_KEY, BODY_READ_TIMEOUT_KEY, LOG_LEVEL_KEY, UNREADY_DELAY_KEY, DRAIN_TIMEOUT_KEY, HEALTH_INTERVAL_KEY,
+    HEALTH_TIMEOUT_KEY, GUARDIAN_CLIENT_ID_KEY, GUARDIAN_CLIENT_SECRET_KEY, GUARDIAN_FORWARD_HEADERS_KEY, GUARDIAN_TIMEOUT_KEY, GUARDIAN_RETRIES_KEY,
+    GUARDIAN_CACHE_TTL_KEY
+];
 
 pub struct State {
     applications: Applications,
-    guardian: Guardian
+    guards: HashMap<String, Arc<dyn Guard>>,
+    tls: Option<TlsSettings>,
+    timeouts: Timeouts,
+    shutdown: ShutdownSettings,
+    health: HealthSettings,
+    guardian_url: Option<String>,
+    trusted_proxies: Arc<Vec<IpNet>>,
+    rules: Arc<Vec<Rule>>,
+    /// Limites de cada aplicação, pelo nome.
+    limits: HashMap<String, Limits>,
+    listeners: Vec<ListenerSettings>,
+    files: Vec<PathBuf>,
+    reload_interval: Option<Duration>,
+    admin: Option<AdminSettings>,
+    catalog: Vec<Entry>,
+    document: Value,
+    hash: String,
+    loaded_at: DateTime
 }
 
 impl State {
@@ -18,46 +62,297 @@ impl State {
         &self.applications
     }
 
-    pub fn guardian(&self) -> &Guardian {
-        &self.guardian
+    pub(crate) fn tls(&self) -> Option<&TlsSettings> {
+        self.tls.as_ref()
     }
-}
 
-pub(crate) fn install_state() -> Result<State> {
-    Ok(create_state(decode_env(read_env(APPLICATION_MAP_KEY)), read_env(GUARDIAN_URL_KEY)))
-}
+    pub(crate) fn timeouts(&self) -> Timeouts {
+        self.timeouts
+    }
+
+    pub(crate) fn shutdown(&self) -> ShutdownSettings {
+        self.shutdown
+    }
+
+    pub(crate) fn health(&self) -> HealthSettings {
+        self.health
+    }
+
+    /// Url do Guardião global, quando configurada.
+    pub(crate) fn guardian_url(&self) -> Option<&str> {
+        self.guardian_url.as_deref()
+    }
+
+    pub(crate) fn listeners(&self) -> &[ListenerSettings] {
+        &self.listeners
+    }
+
+    /// Os endereços da linha de comando viram listeners sem nome, que atendem todas as aplicações
+    /// não restritas a um listener.
+    pub(crate) fn override_listeners(&mut self, addresses: Vec<SocketAddr>) {
+        if !addresses.is_empty() {
+            self.listeners = addresses.into_iter().map(ListenerSettings::tcp).collect();
+        }
+    }
+
+    /// Arquivos de configuração observados; vazio quando a configuração veio das envs.
+    pub(crate) fn files(&self) -> &[PathBuf] {
+        &self.files
+    }
+
+    pub(crate) fn reload_interval(&self) -> Option<Duration> {
+        self.reload_interval
+    }
+
+    pub(crate) fn admin(&self) -> Option<&AdminSettings> {
+        self.admin.as_ref()
+    }
+
+    /// Todas as aplicações conhecidas, inclusive as desativadas pela API de administração.
+    pub(crate) fn catalog(&self) -> &[Entry] {
+        &self.catalog
+    }
+
+    /// Configuração como foi lida do arquivo, ou as envs usadas quando não há arquivo.
+    pub(crate) fn document(&self) -> &Value {
+        &self.document
+    }
 
-fn read_env(env_name: &str) -> String {
-    match env::var(env_name) {
-        Ok(serialized_apps) => serialized_apps,
-        Err(error) => {
-            match error {
-                env::VarError::NotPresent => {
-                    warn!(env_name, "Env Não foi encontrado ambiente");
-                    String::from("[]")
-                },
-                env::VarError::NotUnicode(_) => {
-                    warn!(env_name, "Env Não esta em padrão UNICODE");
-                    String::from("[]")
-                },
+    /// Resumo do documento e das aplicações, para conferir se duas instâncias rodam a mesma configuração.
+    pub(crate) fn hash(&self) -> &str {
+        &self.hash
+    }
+
+    pub(crate) fn loaded_at(&self) -> &DateTime {
+        &self.loaded_at
+    }
+
+    /// Regras globais, avaliadas antes das regras de cada aplicação.
+    pub(crate) fn rules(&self) -> Arc<Vec<Rule>> {
+        self.rules.clone()
+    }
+
+    /// Mantém os limites das aplicações que continuam com a mesma configuração na nova versão.
+    pub(crate) fn inherit(&mut self, previous: &State) {
+        for (name, limits) in &mut self.limits {
+            if let Some(previous) = previous.limits.get(name) {
+                limits.inherit(previous);
             }
         }
     }
+
+    pub(crate) fn gate(&self, app: &Application) -> Result<Gate> {
+        let limits = self.limits.get(&app.domain()).cloned().unwrap_or_else(|| Limits::new(app));
+        Ok(Gate {
+            application: app.clone(),
+            guard: GuardChain::resolve(app.guard(), &self.guards)?,
+            route_guards: app.route_guards()
+                .iter()
+                .map(|(route, strategy)| Ok((route.clone(), GuardChain::resolve(strategy, &self.guards)?)))
+                .collect::<Result<_>>()?,
+            trusted_proxies: self.trusted_proxies.clone(),
+            rate_limiters: limits.rate_limiters,
+            bulkhead: limits.bulkhead
+        })
+    }
 }
 
-fn decode_env(applications: String) -> Applications {
-    match serde_json::from_str(&applications) {
-        Ok(decoded) => decoded,
-        Err(error) => {
-            warn!(applications, "Não foi possivel deserializar env APPLICATIONS error = {}", error);
-            Applications(vec![])
-        }
+/// Valida a configuração inteira antes de montar o estado; qualquer problema impede a subida.
+///
+/// Sem `overlay`, as alterações da API de administração são lidas do armazenamento configurado.
+pub(crate) fn install_state(mut config: Config, overlay: Option<&Overlay>, mut problems: Problems) -> Result<State> {
+    let overlay = match (overlay, &config.admin) {
+        (Some(overlay), _) => overlay.clone(),
+        (None, Some(admin)) => problems.check(Overlay::read(&admin.storage)).unwrap_or_default(),
+        (None, None) => Overlay::default(),
+    };
+    let catalog = overlay.apply(std::mem::take(&mut config.applications));
+    let hash = fingerprint(&config.document, &catalog);
+    let applications = catalog.iter()
+        .filter(|entry| !entry.disabled)
+        .filter_map(|entry| problems.check(decode_application(entry)))
+        .collect::<Vec<Application>>();
+
+    let guardian = config.guardian.take().unwrap_or_else(|| guardian_definition(&mut problems));
+    validation::validate(&config, &applications, &guardian, &mut problems);
+    let guardian_url = Some(guardian.url.clone()).filter(|url| !url.is_empty());
+    let guards = create_guards(Guardian::global(guardian), config.guards, &mut problems);
+    problems.into_result()?;
+
+    let limits = applications.iter().map(|application| (application.domain(), Limits::new(application))).collect();
+    Ok(State {
+        applications: Applications(applications),
+        guards,
+        tls: config.tls,
+        timeouts: config.timeouts.into(),
+        shutdown: config.shutdown,
+        health: config.health,
+        guardian_url,
+        trusted_proxies: Arc::new(config.trusted_proxies),
+        rules: Arc::new(config.rules),
+        limits,
+        listeners: config.listeners,
+        files: config.files,
+        reload_interval: Some(Duration::from_secs(config.reload_interval_seconds)).filter(|interval| !interval.is_zero()),
+        admin: config.admin,
+        catalog,
+        document: config.document,
+        hash,
+        loaded_at: DateTime::now()
+    })
+}
+
+/// O resumo inclui os segredos, para que a troca de um deles também mude o resumo.
+fn fingerprint(document: &Value, catalog: &[Entry]) -> String {
+    let applications = catalog.iter().map(|entry| (&entry.name, entry.disabled, &entry.definition)).collect::<Vec<_>>();
+    let content = serde_json::to_vec(&(document, applications)).unwrap_or_default();
+    hex::encode(Sha256::digest(&content))
+}
+
+/// Configuração montada a partir das envs, usada quando nenhum arquivo foi informado.
+pub(crate) fn env_config(problems: &mut Problems) -> Config {
+    Config {
+        applications: problems.check(secret::env(APPLICATION_MAP_KEY).and_then(decode_applications)).unwrap_or_default(),
+        guards: problems.check(secret::env(GUARDS_KEY).and_then(decode_guards)).unwrap_or_default(),
+        guardian: None,
+        tls: problems.check(secret::env(TLS_KEY).and_then(decode_tls)).flatten(),
+        trusted_proxies: problems.check(secret::env(TRUSTED_PROXIES_KEY).and_then(decode_trusted_proxies)).unwrap_or_default(),
+        rules: problems.check(secret::env(RULES_KEY).and_then(decode_rules)).unwrap_or_default(),
+        timeouts: TimeoutSettings {
+            header_read_ms: parse_env(problems, HEADER_READ_TIMEOUT_KEY).unwrap_or_else(default_header_read_ms),
+            body_read_ms: parse_env(problems, BODY_READ_TIMEOUT_KEY).unwrap_or_else(default_body_read_ms)
+        },
+        shutdown: ShutdownSettings {
+            unready_delay_ms: parse_env(problems, UNREADY_DELAY_KEY).unwrap_or_default(),
+            drain_timeout_ms: parse_env(problems, DRAIN_TIMEOUT_KEY).unwrap_or_else(default_drain_timeout_ms)
+        },
+        health: HealthSettings {
+            interval_ms: parse_env(problems, HEALTH_INTERVAL_KEY).unwrap_or_else(default_health_interval_ms),
+            timeout_ms: parse_env(problems, HEALTH_TIMEOUT_KEY).unwrap_or_else(default_health_timeout_ms)
+        },
+        listeners: problems.check(secret::env(LISTENERS_KEY).and_then(decode_listeners)).unwrap_or_default(),
+        logging: env_logging(),
+        admin: problems.check(secret::env(ADMIN_KEY).and_then(decode_admin)).flatten(),
+        reload_interval_seconds: default_reload_interval_seconds(),
+        files: vec![],
+        document: env_document(problems)
+    }
+}
+
+/// As envs de configuração presentes, com os valores em JSON decodificados.
+fn env_document(problems: &mut Problems) -> Value {
+    let document = CONFIG_KEYS.iter()
+        .filter_map(|key| {
+            let value = read_env(problems, key)?;
+            Some((key.to_string(), serde_json::from_str(&value).unwrap_or(Value::String(value))))
+        })
+        .collect::<Map<String, Value>>();
+    Value::Object(document)
+}
+
+pub(crate) fn env_logging() -> LoggingSettings {
+    env::var(LOG_LEVEL_KEY).map(LoggingSettings::new).unwrap_or_default()
+}
+
+/// Sem `GUARDIAN_URL` a url fica vazia; a validação só reclama se alguma aplicação usar o Guardião.
+fn guardian_definition(problems: &mut Problems) -> GuardianDefinition {
+    let client_secret = problems.check(secret::env_secret(GUARDIAN_CLIENT_SECRET_KEY)).flatten();
+    let introspection = match (read_env(problems, GUARDIAN_CLIENT_ID_KEY), client_secret) {
+        (Some(client_id), Some(client_secret)) => {
+            let cache_ttl_seconds = parse_env(problems, GUARDIAN_CACHE_TTL_KEY).unwrap_or_else(default_cache_ttl_seconds);
+            Some(Introspection::new(client_id, client_secret, cache_ttl_seconds))
+        },
+        _ => None,
+    };
+    let forward = env::var(GUARDIAN_FORWARD_HEADERS_KEY).ok().map(|headers| {
+        ForwardAuth::new(headers.split(',').map(str::trim).filter(|header| !header.is_empty()).map(String::from).collect())
+    });
+
+    GuardianDefinition {
+        url: env::var(GUARDIAN_URL_KEY).unwrap_or_default(),
+        introspection,
+        forward,
+        timeout_ms: parse_env(problems, GUARDIAN_TIMEOUT_KEY).unwrap_or_else(default_timeout_ms),
+        retries: parse_env(problems, GUARDIAN_RETRIES_KEY).unwrap_or_else(default_retries)
+    }
+}
+
+/// Env lida direto ou do arquivo em `<NOME>_FILE`.
+fn read_env(problems: &mut Problems, env_name: &str) -> Option<String> {
+    problems.check(secret::env(env_name)).flatten()
+}
+
+fn parse_env<T: FromStr>(problems: &mut Problems, env_name: &str) -> Option<T> {
+    let value = read_env(problems, env_name)?;
+    let parsed = value.parse().map_err(|_| eyre!("Env {} com valor invalido '{}'", env_name, value));
+    problems.check(parsed)
+}
+
+fn decode_application(entry: &Entry) -> Result<Application> {
+    serde_json::from_value(entry.definition.clone()).map_err(|error| eyre!("Aplicação {} invalida: {}", entry.name, error))
+}
+
+fn decode_applications(applications: Option<String>) -> Result<Vec<Value>> {
+    let applications = applications.ok_or_else(|| eyre!("Env APPLICATIONS não encontrada, informe a env ou um arquivo de configuração"))?;
+    serde_json::from_str(&applications).map_err(|error| eyre!("Não foi possivel deserializar env APPLICATIONS error = {}", error))
+}
+
+fn decode_guards(guards: Option<String>) -> Result<HashMap<String, GuardDefinition>> {
+    match guards {
+        Some(guards) => serde_json::from_str(&guards).map_err(|error| eyre!("Não foi possivel deserializar env GUARDS error = {}", error)),
+        None => Ok(HashMap::new()),
+    }
+}
+
+fn decode_admin(admin: Option<String>) -> Result<Option<AdminSettings>> {
+    match admin {
+        Some(admin) => Ok(Some(serde_json::from_str(&admin).map_err(|error| eyre!("Não foi possivel deserializar env ADMIN error = {}", error))?)),
+        None => Ok(None),
+    }
+}
+
+/// Uma configuração de TLS invalida impede a subida, para não servir HTTP puro por engano.
+fn decode_tls(tls: Option<String>) -> Result<Option<TlsSettings>> {
+    match tls {
+        Some(tls) => Ok(Some(serde_json::from_str(&tls).map_err(|error| eyre!("Não foi possivel deserializar env TLS error = {}", error))?)),
+        None => Ok(None),
+    }
+}
+
+/// Regras invalidas impedem a subida, para não deixar de bloquear o que deveria.
+fn decode_rules(rules: Option<String>) -> Result<Vec<Rule>> {
+    match rules {
+        Some(rules) => serde_json::from_str(&rules).map_err(|error| eyre!("Não foi possivel deserializar env RULES error = {}", error)),
+        None => Ok(vec![]),
     }
 }
 
-fn create_state(apps: Applications, guardian_url: String) -> State {
-    State {
-        applications: apps,
-        guardian: Guardian::new(guardian_url)
+fn decode_listeners(listeners: Option<String>) -> Result<Vec<ListenerSettings>> {
+    match listeners {
+        Some(listeners) => serde_json::from_str(&listeners).map_err(|error| eyre!("Não foi possivel deserializar env LISTENERS error = {}", error)),
+        None => Ok(ListenerSettings::defaults()),
     }
-}
\ No newline at end of file
+}
+
+fn decode_trusted_proxies(proxies: Option<String>) -> Result<Vec<IpNet>> {
+    proxies.unwrap_or_default()
+        .split(',')
+        .map(str::trim)
+        .filter(|proxy| !proxy.is_empty())
+        .map(|proxy| proxy.parse::<IpNet>().map_err(|error| eyre!("Proxy confiavel invalido {}: {}", proxy, error)))
+        .collect()
+}
+
+fn create_guards(guardian: Guardian, definitions: HashMap<String, GuardDefinition>, problems: &mut Problems) -> HashMap<String, Arc<dyn Guard>> {
+    let mut guards: HashMap<String, Arc<dyn Guard>> = HashMap::new();
+    guards.insert(GUARDIAN.to_string(), Arc::new(guardian));
+    guards.insert(NONE.to_string(), Arc::new(NoGuard));
+
+    for (name, definition) in definitions {
+        if let Some(guard) = problems.check(definition.build().map_err(|error| eyre!("Guarda {} invalida: {}", name, error))) {
+            guards.insert(name, guard);
+        }
+    }
+
+    guards
+}
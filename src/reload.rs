@@ -0,0 +1,176 @@
+use std::{collections::VecDeque, future, path::PathBuf, sync::{Arc, Mutex}, time::{Duration, SystemTime}};
+
+use axum::{Router, body::Body, http::Request};
+use color_eyre::{Result, Report};
+use serde::Serialize;
+use tokio::{signal::unix::{signal, SignalKind}, sync::MutexGuard};
+use tower::{ServiceExt, service_fn};
+use tracing::{info, warn};
+
+use crate::{admin::store::Overlay, date::DateTime, management::State, secret};
+
+const HISTORY_SIZE: usize = 20;
+
+/// Uma versão da configuração: o estado e o router montado a partir dele.
+pub(crate) struct Generation {
+    pub state: Arc<State>,
+    pub router: Router
+}
+
+/// O que levou à montagem de uma nova versão.
+#[derive(Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum Trigger {
+    Startup,
+    Signal,
+    Files,
+    Admin
+}
+
+/// Uma tentativa de trocar a versão, aplicada ou recusada.
+#[derive(Clone, Serialize)]
+pub(crate) struct Reload {
+    timestamp: DateTime,
+    trigger: Trigger,
+    outcome: &'static str,
+    hash: Option<String>,
+    error: Option<String>
+}
+
+/// Versão atual do gateway, trocada pelos recarregamentos e pela API de administração.
+///
+/// O `Router` do axum não é `Sync`, então a troca passa por um `Mutex` mantido só durante o clone.
+pub(crate) struct Runtime {
+    source: Option<PathBuf>,
+    current: Mutex<Generation>,
+    exclusive: tokio::sync::Mutex<()>,
+    history: Mutex<VecDeque<Reload>>
+}
+
+impl Runtime {
+    pub(crate) fn new(source: Option<PathBuf>, generation: Generation) -> Arc<Self> {
+        let runtime = Runtime { source, current: Mutex::new(generation), exclusive: tokio::sync::Mutex::new(()), history: Mutex::new(VecDeque::new()) };
+        runtime.record(Trigger::Startup, Ok(&runtime.state()));
+        Arc::new(runtime)
+    }
+
+    /// Origem da configuração: o arquivo informado na subida ou, sem ele, as envs.
+    pub(crate) fn source(&self) -> Option<&PathBuf> {
+        self.source.as_ref()
+    }
+
+    /// Guarda só as últimas tentativas; os erros são mascarados porque a história é exposta pela administração.
+    pub(crate) fn record(&self, trigger: Trigger, result: Result<&State, &Report>) {
+        let reload = Reload {
+            timestamp: DateTime::now(),
+            trigger,
+            outcome: if result.is_ok() { "applied" } else { "rejected" },
+            hash: result.as_ref().ok().map(|state| state.hash().to_string()),
+            error: result.err().map(|error| secret::redact(&error.to_string()))
+        };
+
+        let mut history = self.history.lock().expect("Histórico envenenado");
+        if history.len() == HISTORY_SIZE {
+            history.pop_front();
+        }
+        history.push_back(reload);
+    }
+
+    /// Tentativas de troca da versão, da mais antiga para a mais recente.
+    pub(crate) fn history(&self) -> Vec<Reload> {
+        self.history.lock().expect("Histórico envenenado").iter().cloned().collect()
+    }
+
+    pub(crate) fn state(&self) -> Arc<State> {
+        self.current.lock().expect("Versão atual envenenada").state.clone()
+    }
+
+    fn router(&self) -> Router {
+        self.current.lock().expect("Versão atual envenenada").router.clone()
+    }
+
+    /// Quem monta e troca versões segura este lock, para que as trocas não se atropelem.
+    pub(crate) async fn exclusive(&self) -> MutexGuard<'_, ()> {
+        self.exclusive.lock().await
+    }
+
+    /// Monta uma nova versão da mesma origem da subida, sem trocar a atual.
+    ///
+    /// Sem `overlay`, as alterações da API de administração são lidas do armazenamento.
+    pub(crate) fn build(&self, overlay: Option<&Overlay>) -> Result<Generation> {
+        crate::build(self.source.as_deref(), overlay, &self.state())
+    }
+
+    pub(crate) fn swap(&self, generation: Generation) {
+        *self.current.lock().expect("Versão atual envenenada") = generation;
+    }
+}
+
+/// Router que delega cada requisição à versão atual da configuração.
+///
+/// A versão é lida no inicio da requisição, então requisições em andamento terminam na versão antiga.
+pub(crate) fn swappable(runtime: Arc<Runtime>) -> Router {
+    Router::new().fallback(service_fn(move |request: Request<Body>| runtime.router().oneshot(request)))
+}
+
+/// Recarrega a configuração no SIGHUP ou quando um dos arquivos lidos muda.
+///
+/// Listeners, TLS, timeouts, desligamento, logging e o próprio intervalo de verificação continuam os da subida;
+/// mudanças neles exigem reinicio.
+pub(crate) fn watch(runtime: Arc<Runtime>) {
+    let interval = runtime.state().reload_interval();
+
+    tokio::spawn(async move {
+        let mut hangup = match signal(SignalKind::hangup()) {
+            Ok(hangup) => hangup,
+            Err(error) => {
+                warn!(exception = format!("{:?}", error), "Não foi possivel observar SIGHUP");
+                return;
+            },
+        };
+        let mut last_modified = modified(runtime.state().files());
+
+        loop {
+            let trigger = tokio::select! {
+                _ = hangup.recv() => {
+                    info!("SIGHUP recebido, recarregando configuração");
+                    Trigger::Signal
+                },
+                _ = tick(interval) => {
+                    if modified(runtime.state().files()) == last_modified {
+                        continue;
+                    }
+                    info!("Arquivos de configuração alterados, recarregando");
+                    Trigger::Files
+                },
+            };
+
+            let _exclusive = runtime.exclusive().await;
+            match runtime.build(None) {
+                Ok(generation) => {
+                    info!(applications = generation.state.apps().iter().count(), hash = generation.state.hash(), "Configuração recarregada");
+                    runtime.record(trigger, Ok(&generation.state));
+                    runtime.swap(generation);
+                },
+                Err(error) => {
+                    warn!(exception = format!("{:?}", error), "Não foi possivel recarregar a configuração, mantendo a atual");
+                    runtime.record(trigger, Err(&error));
+                },
+            }
+            last_modified = modified(runtime.state().files());
+        }
+    });
+}
+
+async fn tick(interval: Option<Duration>) {
+    match interval {
+        Some(interval) => tokio::time::sleep(interval).await,
+        None => future::pending().await,
+    }
+}
+
+fn modified(files: &[PathBuf]) -> Vec<Option<SystemTime>> {
+    files.iter()
+        .map(|path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+        .collect()
+}
@@ -2,54 +2,87 @@ use std::{path::PathBuf, collections::HashMap};
 
 use axum::{
     routing::{get, MethodRouter},
-    http::HeaderMap,
-    response::IntoResponse,
-    Router, extract::{Path, Query}, body::Bytes,
+    http::{HeaderMap, StatusCode, Uri},
+    Router, body::Bytes, extract::{Path, Query, RawBody},
 };
-use tracing::info;
+use tracing::{debug, info};
 
-use crate::{gateway::{self, request::{ProxyRequest, ExtractMethod}, guard::Guardian}, applications::Application};
+use crate::gateway::{self, Gate, access, size, pattern, response::ProxyResponse, request::{ProxyRequest, ExtractMethod, ExtractConnection}};
 
+#[allow(clippy::too_many_arguments)]
 async fn redirect(
     ExtractMethod(method): ExtractMethod, 
-    body: Bytes, 
+    RawBody(body): RawBody, 
     query: Query<HashMap<String, String>>, 
     Path(path): Path<String>, 
     headers: HeaderMap,
-    state: Application,
-    guardian: Guardian
-) -> impl IntoResponse {
-    let request = ProxyRequest {
+    uri: Uri,
+    ExtractConnection(connection): ExtractConnection,
+    gate: Gate
+) -> ProxyResponse {
+    let state = &gate.application;
+    if !state.is_served_on(connection.listener.as_deref()) {
+        debug!(application = state.domain(), listener = connection.listener.as_deref(), "Aplicação não atendida neste listener");
+        return ProxyResponse::new(String::new(), StatusCode::NOT_FOUND, HeaderMap::new());
+    }
+
+    // Os controles por rota comparam o caminho exato, então um `..` que o upstream resolveria os contornaria.
+    if pattern::has_dot_segments(&path) {
+        debug!(application = state.domain(), path, "Caminho com segmentos relativos recusado");
+        return gateway::invalid_path_response();
+    }
+
+    if let Err(response) = size::check_head(state, &uri, &headers) {
+        return response;
+    }
+
+    let client_ip = access::client_ip(connection.remote.map(|remote| remote.ip()), &headers, &gate.trusted_proxies);
+    let mut request = ProxyRequest {
         path: PathBuf::from(&path),
         method,
         headers,
-        body,
+        body: Bytes::new(),
         query,
+        uri,
+        connection,
+        client_ip,
         application: state.clone()
     };
 
+    let quota = match gateway::admit(&mut request, &gate) {
+        Ok(quota) => quota,
+        Err(response) => return response,
+    };
+
+    request.body = match size::read_body(state, &request.headers, body, request.connection.body_timeout).await {
+        Ok(body) => body,
+        Err(response) => return response,
+    };
+
     let url =  gateway::to_url(state.endpoint(), PathBuf::from(&path)).unwrap_or_else(|_| "".to_string());
 
-    info!(application = state.domain(), path, url, method = &request.method.to_string(), "New Request");
+    info!(application = state.domain(), path, url, method = &request.method.to_string(), ip = client_ip.map(|ip| ip.to_string()), "New Request");
 
-    gateway::route_to(state.endpoint(), request, guardian).await.unwrap()
+    gateway::route_to(state.endpoint(), request, &gate, quota).await.unwrap()
 }
 
 
-pub fn router(app: Application, guardian: Guardian) -> Router {
-    let path = format!("/{}/*path", app.domain());
-    Router::new().route(&path, default_routes(app, guardian))
+pub(crate) fn router(gate: Gate) -> Router {
+    let path = format!("/{}/*path", gate.application.domain());
+    Router::new().route(&path, default_routes(gate))
 }
 
-fn default_routes(app: Application, guardian: Guardian) -> MethodRouter {
+fn default_routes(gate: Gate) -> MethodRouter {
     let service =  {
         move |
         method, 
         path, 
         body, 
         query, 
-        headers
-        |  redirect(method, path, body, query, headers, app, guardian)
+        headers,
+        uri,
+        connection
+        |  redirect(method, path, body, query, headers, uri, connection, gate)
     };
 
      get(service.clone())
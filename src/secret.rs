@@ -0,0 +1,178 @@
+use std::{env, fmt, fs, path::{Path, PathBuf}, sync::RwLock};
+
+use color_eyre::{Result, eyre::eyre};
+use serde::{Deserialize, Deserializer, Serialize, Serializer, de::Error};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+const FILE_SUFFIX: &str = "_FILE";
+const REDACTED: &str = "[REDACTED]";
+/// Valores mais curtos não são procurados no texto dos logs, para não mascarar trechos comuns.
+const MIN_REDACTED_LENGTH: usize = 4;
+/// Campos sempre mascarados nas saídas da administração, além dos terminados em `secret`.
+const SECRET_FIELDS: [&str; 4] = ["keys", "users", "tokens", "password"];
+
+/// Valores de todos os segredos lidos, mascarados em qualquer linha de log ou saída da administração.
+static KNOWN: RwLock<Vec<String>> = RwLock::new(Vec::new());
+
+/// Valor sensivel da configuração, como senhas, chaves HMAC e credenciais do Guardião.
+///
+/// Aceita o valor direto ou `{ "file": "/run/secrets/nome" }`, e nunca aparece ao ser formatado ou serializado.
+#[derive(Clone)]
+pub(crate) struct Secret(String);
+
+/// Um segredo interpolado de uma env só com dígitos chega como número.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Source {
+    Value(String),
+    Number(serde_json::Number),
+    File { file: PathBuf }
+}
+
+impl Secret {
+    pub(crate) fn new(value: String) -> Self {
+        remember(&value);
+        Secret(value)
+    }
+
+    pub(crate) fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+/// Compara os resumos, sem curto-circuito, para não vazar o segredo pelo tempo de resposta.
+impl PartialEq<str> for Secret {
+    fn eq(&self, other: &str) -> bool {
+        let known = Sha256::digest(self.0.as_bytes());
+        let other = Sha256::digest(other.as_bytes());
+        known.iter().zip(other.iter()).fold(0, |difference, (a, b)| difference | (a ^ b)) == 0
+    }
+}
+
+impl fmt::Debug for Secret {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        formatter.write_str(REDACTED)
+    }
+}
+
+impl Serialize for Secret {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(REDACTED)
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        match Source::deserialize(deserializer)? {
+            Source::Value(value) => Ok(Secret::new(value)),
+            Source::Number(number) => Ok(Secret::new(number.to_string())),
+            Source::File { file } => read(&file).map(Secret::new).map_err(D::Error::custom),
+        }
+    }
+}
+
+/// Lê a env ou, na falta dela, o arquivo indicado em `<NOME>_FILE`, como nos secrets do Docker.
+pub(crate) fn env(name: &str) -> Result<Option<String>> {
+    match (env::var(name), env::var(format!("{}{}", name, FILE_SUFFIX))) {
+        (Ok(value), _) => Ok(Some(value)),
+        (Err(_), Ok(path)) => read(Path::new(&path)).map(Some),
+        (Err(_), Err(_)) => Ok(None),
+    }
+}
+
+/// Como `env`, para as envs citadas na configuração. Valores lidos de `<NOME>_FILE` são segredos
+/// por convenção e passam a ser mascarados onde quer que apareçam, como numa url de upstream.
+pub(crate) fn interpolated(name: &str) -> Result<Option<String>> {
+    let from_file = env::var(name).is_err();
+    let value = self::env(name)?;
+    if let Some(value) = value.as_deref().filter(|_| from_file) {
+        remember(value);
+    }
+    Ok(value)
+}
+
+/// Como `env`, para envs que guardam segredos.
+pub(crate) fn env_secret(name: &str) -> Result<Option<Secret>> {
+    Ok(env(name)?.map(Secret::new))
+}
+
+/// A quebra de linha final, comum em arquivos de segredo, não faz parte do valor.
+fn read(path: &Path) -> Result<String> {
+    let value = fs::read_to_string(path).map_err(|error| eyre!("Não foi possivel ler o segredo em {}: {}", path.display(), error))?;
+    Ok(value.trim_end_matches(['\n', '\r']).to_string())
+}
+
+pub(crate) fn remember(value: &str) {
+    if value.chars().count() < MIN_REDACTED_LENGTH {
+        return;
+    }
+    let mut known = KNOWN.write().expect("Segredos envenenados");
+    if !known.iter().any(|known| known == value) {
+        known.push(value.to_string());
+    }
+}
+
+/// Mascara os segredos conhecidos no texto, inclusive na forma escapada em JSON.
+pub fn redact(text: &str) -> String {
+    let known = KNOWN.read().expect("Segredos envenenados");
+    known.iter().fold(text.to_string(), |text, secret| {
+        let escaped = serde_json::to_string(secret).unwrap_or_default();
+        text.replace(secret.as_str(), REDACTED).replace(escaped.trim_matches('"'), REDACTED)
+    })
+}
+
+/// Serializa o valor com os segredos mascarados, para as respostas da administração.
+pub(crate) fn redacted<S: Serializer>(value: &Value, serializer: S) -> Result<S::Ok, S::Error> {
+    redact_value(value).serialize(serializer)
+}
+
+/// Mascara os segredos conhecidos em todas as strings e, por inteiro, os campos de segredo,
+/// que podem ter valores curtos demais para a busca no texto.
+pub(crate) fn redact_value(value: &Value) -> Value {
+    match value {
+        Value::String(text) => Value::String(redact(text)),
+        Value::Array(values) => Value::Array(values.iter().map(redact_value).collect()),
+        Value::Object(values) => Value::Object(values.iter()
+            .map(|(name, value)| (name.clone(), if is_secret_field(name) { mask(value) } else { redact_value(value) }))
+            .collect()),
+        value => value.clone(),
+    }
+}
+
+pub(crate) fn is_secret_field(name: &str) -> bool {
+    let name = name.to_lowercase();
+    name.ends_with("secret") || SECRET_FIELDS.contains(&name.as_str())
+}
+
+/// Mantém a forma do valor, como os nomes dos usuários, trocando cada folha pela marca.
+fn mask(value: &Value) -> Value {
+    match value {
+        Value::Array(values) => Value::Array(values.iter().map(mask).collect()),
+        Value::Object(values) => Value::Object(values.iter().map(|(name, value)| (name.clone(), mask(value))).collect()),
+        Value::Null => Value::Null,
+        _ => Value::String(REDACTED.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compares_with_the_exposed_value() {
+        let secret = Secret::new("chave-de-teste".to_string());
+        assert!(secret == *"chave-de-teste");
+        assert!(secret != *"chave-de-test");
+        assert!(secret != *"chave-de-teste-");
+        assert!(secret != *"");
+    }
+
+    #[test]
+    fn never_shows_the_value() {
+        let secret = Secret::new("senha-do-guardiao".to_string());
+        assert_eq!(format!("{:?}", secret), REDACTED);
+        assert_eq!(serde_json::to_string(&secret).unwrap(), format!("\"{}\"", REDACTED));
+        assert_eq!(redact("erro com senha-do-guardiao no meio"), format!("erro com {} no meio", REDACTED));
+    }
+}
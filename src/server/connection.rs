@@ -0,0 +1,65 @@
+use std::{net::{IpAddr, SocketAddr}, time::Duration};
+
+use axum::http::{HeaderMap, HeaderValue};
+use rustls::Certificate;
+use sha2::{Digest, Sha256};
+use x509_parser::{extensions::GeneralName, parse_x509_certificate};
+
+pub(crate) const SUBJECT_HEADER: &str = "X-Client-Cert-Subject";
+pub(crate) const SAN_HEADER: &str = "X-Client-Cert-San";
+pub(crate) const FINGERPRINT_HEADER: &str = "X-Client-Cert-Fingerprint";
+
+/// Certificado de cliente já verificado contra a CA configurada no TLS.
+#[derive(Clone)]
+pub(crate) struct ClientCertificate {
+    pub subject: String,
+    pub sans: Vec<String>,
+    pub fingerprint: String
+}
+
+impl ClientCertificate {
+    pub(crate) fn parse(certificate: &Certificate) -> Option<Self> {
+        let (_, parsed) = parse_x509_certificate(&certificate.0).ok()?;
+        let sans = parsed.subject_alternative_name().ok().flatten()
+            .map(|extension| extension.value.general_names.iter().filter_map(general_name).collect())
+            .unwrap_or_default();
+
+        Some(ClientCertificate {
+            subject: parsed.subject().to_string(),
+            sans,
+            fingerprint: hex::encode(Sha256::digest(&certificate.0))
+        })
+    }
+
+    pub(crate) fn apply(&self, headers: &mut HeaderMap) {
+        let fields = [(SUBJECT_HEADER, self.subject.clone()), (SAN_HEADER, self.sans.join(",")), (FINGERPRINT_HEADER, self.fingerprint.clone())];
+        for (header, value) in fields {
+            if let Ok(value) = HeaderValue::from_str(&value) {
+                headers.insert(header, value);
+            }
+        }
+    }
+}
+
+fn general_name(name: &GeneralName) -> Option<String> {
+    match name {
+        GeneralName::DNSName(name) | GeneralName::RFC822Name(name) | GeneralName::URI(name) => Some(name.to_string()),
+        GeneralName::IPAddress(bytes) => match bytes.len() {
+            4 => Some(IpAddr::from(<[u8; 4]>::try_from(*bytes).ok()?).to_string()),
+            16 => Some(IpAddr::from(<[u8; 16]>::try_from(*bytes).ok()?).to_string()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Informações da conexão que originou a requisição.
+#[derive(Clone, Default)]
+pub(crate) struct Connection {
+    pub remote: Option<SocketAddr>,
+    /// Nome do listener que aceitou a conexão, quando ele tem um.
+    pub listener: Option<String>,
+    pub client_certificate: Option<ClientCertificate>,
+    /// Tempo máximo para o cliente enviar o corpo da requisição.
+    pub body_timeout: Option<Duration>
+}
@@ -0,0 +1,120 @@
+use std::{env, fs, net::SocketAddr, os::unix::{fs::{FileTypeExt, PermissionsExt}, io::{FromRawFd, IntoRawFd, RawFd}}, path::PathBuf};
+
+use color_eyre::{Result, eyre::eyre};
+use serde::Deserialize;
+use tokio::net::{TcpListener, UnixListener};
+
+const LISTEN_FDS_KEY: &str = "LISTEN_FDS";
+const LISTEN_PID_KEY: &str = "LISTEN_PID";
+/// Primeiro descritor entregue pelo systemd na ativação por socket.
+const LISTEN_FDS_START: RawFd = 3;
+const DEFAULT_LISTEN_ADDRESS: ([u8; 4], u16) = ([0, 0, 0, 0], 8080);
+
+/// Onde o gateway escuta: um endereço TCP, um socket Unix ou um socket recebido do systemd.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct ListenerSettings {
+    /// Nome usado pelas aplicações que só devem ser atendidas neste listener.
+    pub name: Option<String>,
+    pub address: Option<SocketAddr>,
+    pub unix: Option<PathBuf>,
+    /// Permissões do socket Unix em octal, como "660".
+    pub mode: Option<String>,
+    /// Posição do socket entre os recebidos por ativação do systemd (`LISTEN_FDS`).
+    pub systemd: Option<usize>,
+    /// Se o listener termina TLS. Sem o campo, termina quando a seção `tls` está configurada;
+    /// `false` deixa um listener interno, como um socket Unix, em texto puro.
+    pub tls: Option<bool>
+}
+
+pub(crate) enum Listener {
+    Tcp(TcpListener),
+    Unix(UnixListener)
+}
+
+impl ListenerSettings {
+    pub(crate) fn tcp(address: SocketAddr) -> Self {
+        ListenerSettings { name: None, address: Some(address), unix: None, mode: None, systemd: None, tls: None }
+    }
+
+    pub(crate) fn uses_tls(&self) -> bool {
+        self.tls.unwrap_or(true)
+    }
+
+    pub(crate) fn defaults() -> Vec<Self> {
+        vec![Self::tcp(SocketAddr::from(DEFAULT_LISTEN_ADDRESS))]
+    }
+
+    pub(crate) fn describe(&self) -> String {
+        match (&self.address, &self.unix, &self.systemd) {
+            (Some(address), _, _) => address.to_string(),
+            (_, Some(path), _) => format!("unix:{}", path.display()),
+            (_, _, Some(index)) => format!("systemd:{}", index),
+            _ => "-".to_string(),
+        }
+    }
+
+    /// Exige exatamente uma origem de socket e permissões validas.
+    pub(crate) fn check(&self) -> Result<()> {
+        let sources = [self.address.is_some(), self.unix.is_some(), self.systemd.is_some()].iter().filter(|source| **source).count();
+        if sources != 1 {
+            return Err(eyre!("Listener {} precisa de exatamente um entre address, unix e systemd", self.describe()));
+        }
+        if self.mode.is_some() && self.unix.is_none() {
+            return Err(eyre!("Listener {} só aceita mode com socket Unix", self.describe()));
+        }
+        self.mode().map(|_| ())
+    }
+
+    fn mode(&self) -> Result<Option<u32>> {
+        self.mode.as_deref()
+            .map(|mode| u32::from_str_radix(mode, 8).map_err(|_| eyre!("Permissões invalidas '{}' no listener {}, use octal como 660", mode, self.describe())))
+            .transpose()
+    }
+
+    pub(crate) async fn bind(&self) -> Result<Listener> {
+        self.check()?;
+        match (&self.address, &self.unix, &self.systemd) {
+            (Some(address), _, _) => Ok(Listener::Tcp(TcpListener::bind(address).await?)),
+            (_, Some(path), _) => self.bind_unix(path),
+            (_, _, Some(index)) => systemd(*index),
+            _ => Err(eyre!("Listener sem endereço")),
+        }
+    }
+
+    /// Um socket esquecido por uma execução anterior é removido antes do bind.
+    fn bind_unix(&self, path: &PathBuf) -> Result<Listener> {
+        if fs::symlink_metadata(path).map(|metadata| metadata.file_type().is_socket()).unwrap_or(false) {
+            fs::remove_file(path)?;
+        }
+
+        let listener = UnixListener::bind(path)?;
+        if let Some(mode) = self.mode()? {
+            fs::set_permissions(path, fs::Permissions::from_mode(mode))?;
+        }
+        Ok(Listener::Unix(listener))
+    }
+}
+
+/// Socket recebido por ativação do systemd. O tipo é descoberto pelo endereço local:
+/// só sockets TCP têm um `SocketAddr`.
+fn systemd(index: usize) -> Result<Listener> {
+    let pid = env::var(LISTEN_PID_KEY).ok().and_then(|pid| pid.parse::<u32>().ok());
+    let count = env::var(LISTEN_FDS_KEY).ok().and_then(|count| count.parse::<usize>().ok()).unwrap_or_default();
+    if pid != Some(std::process::id()) || index >= count {
+        return Err(eyre!("Socket {} do systemd não foi recebido, LISTEN_FDS={}", index, count));
+    }
+
+    // SAFETY: o systemd entrega os descritores a partir do 3 para este processo, e a validação
+    // impede que dois listeners usem o mesmo indice.
+    let listener = unsafe { std::net::TcpListener::from_raw_fd(LISTEN_FDS_START + index as RawFd) };
+    if listener.local_addr().is_ok() {
+        listener.set_nonblocking(true)?;
+        return Ok(Listener::Tcp(TcpListener::from_std(listener)?));
+    }
+
+    // SAFETY: o descritor veio do listener acima, que deixa de ser dono dele no `into_raw_fd`.
+    let listener = unsafe { std::os::unix::net::UnixListener::from_raw_fd(listener.into_raw_fd()) };
+    listener.set_nonblocking(true)?;
+    Ok(Listener::Unix(UnixListener::from_std(listener)?))
+}
@@ -0,0 +1,124 @@
+use std::{io::{self, ErrorKind}, net::SocketAddr, pin::Pin, sync::Arc, time::Duration};
+
+use axum::{Router, Extension};
+use color_eyre::Result;
+use hyper::server::conn::Http;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_rustls::TlsAcceptor;
+use tower::Layer;
+use tracing::{debug, info, warn};
+
+use crate::shutdown::Shutdown;
+
+use self::{connection::{Connection, ClientCertificate}, listener::{Listener, ListenerSettings}};
+
+pub(crate) mod tls;
+pub(crate) mod connection;
+pub(crate) mod listener;
+
+const ACCEPT_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Tempos máximos de leitura do listener, contra clientes lentos no estilo slowloris.
+#[derive(Clone, Copy)]
+pub(crate) struct Timeouts {
+    /// Vale para o handshake TLS e para a leitura dos headers.
+    pub header_read: Duration,
+    pub body_read: Duration
+}
+
+/// Aceita conexões no listener informado, terminando TLS quando ele recebe o acceptor.
+///
+/// Termina quando o desligamento fecha os listeners; as conexões já aceitas seguem até serem drenadas.
+pub(crate) async fn serve(app: Router, settings: ListenerSettings, acceptor: Option<TlsAcceptor>, timeouts: Timeouts, shutdown: Arc<Shutdown>) -> Result<()> {
+    let listener = settings.bind().await?;
+    info!(tls = acceptor.is_some(), listener = settings.name.as_deref(), "listening on {}", settings.describe());
+
+    let name = settings.name.clone();
+    match listener {
+        Listener::Tcp(listener) => loop {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                _ = shutdown.stopped() => break,
+            };
+            match accepted {
+                Ok((stream, remote)) => {
+                    tokio::spawn(accept(stream, Some(remote), name.clone(), app.clone(), acceptor.clone(), timeouts, shutdown.clone()));
+                },
+                Err(error) => recover(error, &settings).await,
+            }
+        },
+        Listener::Unix(listener) => loop {
+            let accepted = tokio::select! {
+                accepted = listener.accept() => accepted,
+                _ = shutdown.stopped() => break,
+            };
+            match accepted {
+                Ok((stream, _)) => {
+                    tokio::spawn(accept(stream, None, name.clone(), app.clone(), acceptor.clone(), timeouts, shutdown.clone()));
+                },
+                Err(error) => recover(error, &settings).await,
+            }
+        },
+    }
+
+    info!(listener = settings.name.as_deref(), "Parou de escutar em {}", settings.describe());
+    Ok(())
+}
+
+/// Erros do accept não derrubam o listener. Os da própria conexão são ignorados, e os demais, como falta
+/// de descritores sob carga, ganham uma pausa antes da próxima tentativa, como no `AddrIncoming` do hyper.
+async fn recover(error: io::Error, settings: &ListenerSettings) {
+    if matches!(error.kind(), ErrorKind::ConnectionAborted | ErrorKind::ConnectionReset | ErrorKind::ConnectionRefused) {
+        debug!(listener = settings.name.as_deref(), exception = format!("{:?}", error), "Conexão perdida antes de ser aceita");
+        return;
+    }
+
+    warn!(listener = settings.name.as_deref(), exception = format!("{:?}", error), backoff_ms = ACCEPT_BACKOFF.as_millis() as u64, "Erro ao aceitar conexão em {}", settings.describe());
+    tokio::time::sleep(ACCEPT_BACKOFF).await;
+}
+
+/// Conexões de socket Unix não têm endereço remoto; o processo do outro lado é tratado como proxy confiavel
+/// e o IP do cliente vem do `X-Forwarded-For`.
+async fn accept<S>(stream: S, remote: Option<SocketAddr>, listener: Option<String>, app: Router, acceptor: Option<TlsAcceptor>, timeouts: Timeouts, shutdown: Arc<Shutdown>)
+where S: AsyncRead + AsyncWrite + Unpin + Send + 'static
+{
+    let _tracked = shutdown.track();
+    let peer = remote.map(|remote| remote.to_string()).unwrap_or_else(|| "unix".to_string());
+    match acceptor {
+        Some(acceptor) => match tokio::time::timeout(timeouts.header_read, acceptor.accept(stream)).await {
+            Ok(Ok(stream)) => {
+                let client_certificate = stream.get_ref().1.peer_certificates()
+                    .and_then(|certificates| certificates.first())
+                    .and_then(ClientCertificate::parse);
+                connection(stream, app, Connection { remote, listener, client_certificate, body_timeout: Some(timeouts.body_read) }, timeouts, &shutdown).await
+            },
+            Ok(Err(error)) => debug!(remote = peer, exception = format!("{:?}", error), "Handshake TLS falhou"),
+            Err(_) => debug!(remote = peer, "Tempo do handshake TLS esgotado"),
+        },
+        None => connection(stream, app, Connection { remote, listener, client_certificate: None, body_timeout: Some(timeouts.body_read) }, timeouts, &shutdown).await,
+    }
+}
+
+/// No desligamento a conexão termina a requisição em andamento e fecha, em vez de esperar a próxima.
+async fn connection<S>(stream: S, app: Router, connection: Connection, timeouts: Timeouts, shutdown: &Shutdown)
+where S: AsyncRead + AsyncWrite + Unpin + Send + 'static
+{
+    let service = Extension(connection).layer(app);
+    let mut http = Http::new();
+    http.http1_header_read_timeout(timeouts.header_read);
+    let mut serving = http.serve_connection(stream, service).with_upgrades();
+    let finished = tokio::select! {
+        result = &mut serving => Some(result),
+        _ = shutdown.stopped() => None,
+    };
+    let result = match finished {
+        Some(result) => result,
+        None => {
+            Pin::new(&mut serving).graceful_shutdown();
+            serving.await
+        },
+    };
+    if let Err(error) = result {
+        debug!(exception = format!("{:?}", error), "Conexão encerrada com erro");
+    }
+}
@@ -0,0 +1,233 @@
+use std::{collections::HashMap, fs::File, io::BufReader, path::PathBuf, sync::{Arc, RwLock}, time::{Duration, SystemTime}};
+
+use color_eyre::{Result, eyre::eyre};
+use rustls::{
+    Certificate, PrivateKey, RootCertStore, ServerConfig, SupportedProtocolVersion,
+    server::{AllowAnyAnonymousOrAuthenticatedClient, ClientHello, ResolvesServerCert},
+    sign::{self, CertifiedKey},
+    version::{TLS12, TLS13},
+};
+use rustls_pemfile::Item;
+use serde::Deserialize;
+use tokio_rustls::TlsAcceptor;
+use tracing::{info, warn};
+
+const DEFAULT_RELOAD_INTERVAL_SECONDS: u64 = 30;
+
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct CertificateSettings {
+    cert: PathBuf,
+    key: PathBuf,
+    /// Nomes atendidos por este certificado via SNI. Sem nomes, o certificado é o padrão; só um pode ficar sem nomes.
+    #[serde(default)]
+    hostnames: Vec<String>
+}
+
+/// Configuração de TLS do listener, lida da env `TLS`.
+#[derive(Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub(crate) struct TlsSettings {
+    certificates: Vec<CertificateSettings>,
+    #[serde(default = "default_versions")]
+    versions: Vec<String>,
+    #[serde(default = "default_alpn")]
+    alpn: Vec<String>,
+    #[serde(default = "default_reload_interval_seconds")]
+    reload_interval_seconds: u64,
+    /// CA que assina os certificados de cliente. Com ela o cliente pode se autenticar via mTLS.
+    client_ca: Option<PathBuf>
+}
+
+fn default_versions() -> Vec<String> {
+    vec!["1.2".to_string(), "1.3".to_string()]
+}
+
+fn default_alpn() -> Vec<String> {
+    vec!["h2".to_string(), "http/1.1".to_string()]
+}
+
+fn default_reload_interval_seconds() -> u64 {
+    DEFAULT_RELOAD_INTERVAL_SECONDS
+}
+
+impl TlsSettings {
+    fn versions(&self) -> Result<Vec<&'static SupportedProtocolVersion>> {
+        self.versions.iter().map(|version| match version.as_str() {
+            "1.2" => Ok(&TLS12),
+            "1.3" => Ok(&TLS13),
+            other => Err(eyre!("Versão de TLS não suportada: {}", other)),
+        }).collect()
+    }
+
+    /// CAs dos certificados de cliente, quando o mTLS está configurado.
+    fn client_roots(&self) -> Result<Option<RootCertStore>> {
+        let path = match &self.client_ca {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        let mut roots = RootCertStore::empty();
+        for item in read_pem(path)? {
+            if let Item::X509Certificate(der) = item {
+                roots.add(&Certificate(der))?;
+            }
+        }
+
+        if roots.is_empty() {
+            return Err(eyre!("Nenhuma CA encontrada em {:?}", path));
+        }
+
+        Ok(Some(roots))
+    }
+
+    /// Carrega versões, certificados e CA como a subida faria, para que a validação já acuse arquivos faltando.
+    pub(crate) fn check(&self) -> Result<()> {
+        self.versions()?;
+        Certificates::load(self)?;
+        self.client_roots()?;
+        Ok(())
+    }
+
+    fn paths(&self) -> impl Iterator<Item = &PathBuf> {
+        self.certificates.iter().flat_map(|certificate| [&certificate.cert, &certificate.key])
+    }
+}
+
+struct Certificates {
+    by_name: HashMap<String, Arc<CertifiedKey>>,
+    default: Arc<CertifiedKey>
+}
+
+impl Certificates {
+    fn load(settings: &TlsSettings) -> Result<Self> {
+        if settings.certificates.iter().filter(|certificate| certificate.hostnames.is_empty()).count() > 1 {
+            return Err(eyre!("Só um certificado TLS pode ficar sem hostnames"));
+        }
+
+        let mut by_name = HashMap::new();
+        let mut first = None;
+        let mut default = None;
+
+        for certificate in &settings.certificates {
+            let key = Arc::new(certified_key(certificate)?);
+            if certificate.hostnames.is_empty() {
+                default = Some(key.clone());
+            }
+            for hostname in &certificate.hostnames {
+                by_name.insert(hostname.to_lowercase(), key.clone());
+            }
+            first = first.or(Some(key));
+        }
+
+        // Sem um certificado sem hostnames, o primeiro da lista atende os nomes desconhecidos.
+        let default = default.or(first).ok_or_else(|| eyre!("TLS configurado sem certificados"))?;
+        Ok(Certificates { by_name, default })
+    }
+}
+
+/// Escolhe o certificado pelo SNI, e permite trocar os certificados sem recriar o `ServerConfig`.
+pub(crate) struct CertificateStore {
+    certificates: RwLock<Certificates>
+}
+
+impl ResolvesServerCert for CertificateStore {
+    fn resolve(&self, client_hello: ClientHello) -> Option<Arc<CertifiedKey>> {
+        let certificates = self.certificates.read().expect("Certificados envenenados");
+        let by_name = client_hello.server_name().and_then(|name| certificates.by_name.get(&name.to_lowercase()));
+        Some(by_name.unwrap_or(&certificates.default).clone())
+    }
+}
+
+/// Acceptor compartilhado pelos listeners com TLS, com os certificados recarregados por um único `watch`.
+pub(crate) fn shared(settings: &TlsSettings) -> Result<TlsAcceptor> {
+    let (acceptor, store) = acceptor(settings)?;
+    watch(settings.clone(), store);
+    Ok(acceptor)
+}
+
+fn acceptor(settings: &TlsSettings) -> Result<(TlsAcceptor, Arc<CertificateStore>)> {
+    let store = Arc::new(CertificateStore { certificates: RwLock::new(Certificates::load(settings)?) });
+
+    let builder = ServerConfig::builder()
+        .with_safe_default_cipher_suites()
+        .with_safe_default_kx_groups()
+        .with_protocol_versions(&settings.versions()?)?;
+    // O certificado de cliente é opcional no handshake: quem exige mTLS é a guarda da aplicação ou rota.
+    let builder = match settings.client_roots()? {
+        Some(roots) => builder.with_client_cert_verifier(AllowAnyAnonymousOrAuthenticatedClient::new(roots)),
+        None => builder.with_no_client_auth(),
+    };
+    let mut config = builder.with_cert_resolver(store.clone());
+    config.alpn_protocols = settings.alpn.iter().map(|protocol| protocol.as_bytes().to_vec()).collect();
+
+    Ok((TlsAcceptor::from(Arc::new(config)), store))
+}
+
+/// Recarrega os certificados quando algum arquivo muda no disco; `reload_interval_seconds` zero desliga a verificação.
+///
+/// Se a recarga falhar, os certificados atuais continuam em uso.
+fn watch(settings: TlsSettings, store: Arc<CertificateStore>) {
+    if settings.reload_interval_seconds == 0 {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(settings.reload_interval_seconds));
+        let mut last_modified = modified(&settings);
+
+        loop {
+            interval.tick().await;
+            let modified = modified(&settings);
+            if modified == last_modified {
+                continue;
+            }
+            last_modified = modified;
+
+            match Certificates::load(&settings) {
+                Ok(certificates) => {
+                    *store.certificates.write().expect("Certificados envenenados") = certificates;
+                    info!("Certificados TLS recarregados");
+                },
+                Err(error) => warn!(exception = format!("{:?}", error), "Não foi possivel recarregar certificados TLS"),
+            }
+        }
+    });
+}
+
+fn modified(settings: &TlsSettings) -> Vec<Option<SystemTime>> {
+    settings.paths()
+        .map(|path| std::fs::metadata(path).and_then(|metadata| metadata.modified()).ok())
+        .collect()
+}
+
+fn certified_key(settings: &CertificateSettings) -> Result<CertifiedKey> {
+    let certificates = read_pem(&settings.cert)?
+        .into_iter()
+        .filter_map(|item| match item {
+            Item::X509Certificate(der) => Some(Certificate(der)),
+            _ => None,
+        })
+        .collect::<Vec<Certificate>>();
+
+    if certificates.is_empty() {
+        return Err(eyre!("Nenhum certificado encontrado em {:?}", settings.cert));
+    }
+
+    let key = read_pem(&settings.key)?
+        .into_iter()
+        .find_map(|item| match item {
+            Item::PKCS8Key(der) | Item::RSAKey(der) | Item::ECKey(der) => Some(PrivateKey(der)),
+            _ => None,
+        })
+        .ok_or_else(|| eyre!("Nenhuma chave privada encontrada em {:?}", settings.key))?;
+
+    let key = sign::any_supported_type(&key).map_err(|_| eyre!("Chave privada não suportada em {:?}", settings.key))?;
+    Ok(CertifiedKey::new(certificates, key))
+}
+
+fn read_pem(path: &PathBuf) -> Result<Vec<Item>> {
+    let file = File::open(path).map_err(|error| eyre!("Não foi possivel abrir {:?}: {}", path, error))?;
+    let mut reader = BufReader::new(file);
+    Ok(rustls_pemfile::read_all(&mut reader)?)
+}
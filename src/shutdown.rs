@@ -0,0 +1,141 @@
+use std::{sync::{Arc, atomic::{AtomicBool, AtomicUsize, Ordering}}, time::{Duration, Instant}};
+
+use axum::{body::Body, http::Request, middleware::Next, response::Response};
+use tokio::{signal::unix::{signal, SignalKind}, sync::watch};
+use tracing::{info, warn};
+
+use crate::config::ShutdownSettings;
+
+/// Desligamento do gateway, compartilhado entre os listeners, as conexões e o health check.
+///
+/// No SIGTERM ou SIGINT o gateway primeiro se declara indisponivel, depois para de aceitar conexões
+/// e espera as em andamento até o tempo de drenagem.
+pub(crate) struct Shutdown {
+    settings: ShutdownSettings,
+    draining: AtomicBool,
+    stopped: watch::Sender<bool>,
+    connections: watch::Sender<usize>,
+    requests: AtomicUsize
+}
+
+/// Mantém a conexão contada enquanto existir.
+pub(crate) struct Tracked(Arc<Shutdown>);
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        self.0.connections.send_modify(|connections| *connections -= 1);
+    }
+}
+
+impl Shutdown {
+    pub(crate) fn new(settings: ShutdownSettings) -> Arc<Self> {
+        Arc::new(Shutdown {
+            settings,
+            draining: AtomicBool::new(false),
+            stopped: watch::channel(false).0,
+            connections: watch::channel(0).0,
+            requests: AtomicUsize::new(0)
+        })
+    }
+
+    pub(crate) fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    /// Termina quando os listeners devem parar de aceitar conexões.
+    pub(crate) async fn stopped(&self) {
+        let mut stopped = self.stopped.subscribe();
+        while !*stopped.borrow() {
+            if stopped.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    pub(crate) fn track(self: &Arc<Self>) -> Tracked {
+        self.connections.send_modify(|connections| *connections += 1);
+        Tracked(self.clone())
+    }
+
+    async fn idle(&self) {
+        let mut connections = self.connections.subscribe();
+        while *connections.borrow() > 0 {
+            if connections.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+
+    /// Espera o SIGTERM ou SIGINT, marca o gateway como indisponivel e, passado o atraso configurado,
+    /// fecha os listeners.
+    pub(crate) fn listen(self: &Arc<Self>) {
+        let shutdown = self.clone();
+        tokio::spawn(async move {
+            let (mut terminate, mut interrupt) = match (signal(SignalKind::terminate()), signal(SignalKind::interrupt())) {
+                (Ok(terminate), Ok(interrupt)) => (terminate, interrupt),
+                (Err(error), _) | (_, Err(error)) => {
+                    warn!(exception = format!("{:?}", error), "Não foi possivel observar SIGTERM e SIGINT");
+                    return;
+                },
+            };
+
+            let signal = tokio::select! {
+                _ = terminate.recv() => "SIGTERM",
+                _ = interrupt.recv() => "SIGINT",
+            };
+            info!(signal, delay_ms = shutdown.settings.unready_delay_ms, "Sinal de desligamento recebido, gateway marcado como indisponivel");
+            shutdown.draining.store(true, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(shutdown.settings.unready_delay_ms)).await;
+            shutdown.stopped.send_replace(true);
+        });
+    }
+
+    /// Espera as conexões abertas terminarem, até o tempo de drenagem, e registra o resumo.
+    ///
+    /// Conexões que sobram, inclusive as promovidas a WebSocket, são encerradas junto com o processo.
+    pub(crate) async fn drain(&self) {
+        let started = Instant::now();
+        let connections = *self.connections.borrow();
+        let requests = self.requests.load(Ordering::SeqCst);
+        info!(connections, requests, timeout_ms = self.settings.drain_timeout_ms, "Listeners fechados, aguardando as conexões em andamento");
+
+        let drained = tokio::time::timeout(Duration::from_millis(self.settings.drain_timeout_ms), self.idle()).await.is_ok();
+        let remaining = *self.connections.borrow();
+        let remaining_requests = self.requests.load(Ordering::SeqCst);
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        match drained {
+            true => info!(connections, requests, elapsed_ms, "Desligamento concluído, todas as conexões drenadas"),
+            false => warn!(
+                connections,
+                requests,
+                drained = connections.saturating_sub(remaining),
+                remaining,
+                remaining_requests,
+                elapsed_ms,
+                "Tempo de drenagem esgotado, encerrando as conexões restantes"
+            ),
+        }
+    }
+}
+
+/// Conta as requisições em andamento, para o resumo do desligamento.
+pub(crate) async fn count(shutdown: Arc<Shutdown>, request: Request<Body>, next: Next<Body>) -> Response {
+    let _counted = Counted::new(&shutdown.requests);
+    next.run(request).await
+}
+
+/// Desconta a requisição mesmo quando o cliente desiste dela no meio.
+struct Counted<'a>(&'a AtomicUsize);
+
+impl<'a> Counted<'a> {
+    fn new(counter: &'a AtomicUsize) -> Self {
+        counter.fetch_add(1, Ordering::SeqCst);
+        Counted(counter)
+    }
+}
+
+impl Drop for Counted<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
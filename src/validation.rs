@@ -0,0 +1,238 @@
+use std::{collections::HashSet, path::Path};
+
+use color_eyre::{Result, eyre::eyre};
+use reqwest::Url;
+
+use crate::{applications::{Application, GuardStrategy}, config::Config, gateway::guard::{GuardDefinition, guardian::GuardianDefinition, GUARDIAN, NONE}};
+
+/// Nomes de aplicação que colidiriam com as rotas do próprio gateway.
+const RESERVED_NAMES: [&str; 1] = ["health"];
+
+/// Problemas encontrados na configuração, reportados todos de uma vez para não exigir
+/// uma subida por erro corrigido.
+#[derive(Default)]
+pub(crate) struct Problems(Vec<String>);
+
+impl Problems {
+    pub(crate) fn push(&mut self, problem: String) {
+        self.0.push(problem);
+    }
+
+    pub(crate) fn check<T>(&mut self, result: Result<T>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                self.push(error.to_string());
+                None
+            },
+        }
+    }
+
+    pub(crate) fn into_result(self) -> Result<()> {
+        if self.0.is_empty() {
+            return Ok(());
+        }
+
+        let problems = self.0.iter().map(|problem| format!("  - {}", problem)).collect::<Vec<String>>().join("\n");
+        Err(eyre!("Configuração invalida, {} problema(s):\n{}", self.0.len(), problems))
+    }
+}
+
+pub(crate) fn validate(config: &Config, applications: &[Application], guardian: &GuardianDefinition, problems: &mut Problems) {
+    let mut domains = HashSet::new();
+    let mut guardian_users = vec![];
+    let admin_prefix = admin(config, problems);
+    let listener_names = listeners(config, problems);
+    health(config, problems);
+    if let Some(tls) = &config.tls {
+        if let Err(error) = tls.check() {
+            problems.push(format!("TLS invalido: {}", error));
+        }
+    }
+    for rule in &config.rules {
+        if let Err(error) = rule.check() {
+            problems.push(format!("{} nas regras globais", error));
+        }
+    }
+
+    for application in applications {
+        let domain = application.domain();
+        if domain.is_empty() || !domain.chars().all(|char| char.is_ascii_alphanumeric() || "-_.".contains(char)) {
+            problems.push(format!("Nome de aplicação invalido '{}', use letras, números, '-', '_' ou '.'", domain));
+        }
+        if RESERVED_NAMES.contains(&domain.as_str()) {
+            problems.push(format!("Aplicação {} colide com a rota /{} do gateway", domain, domain));
+        }
+        if admin_prefix.as_deref() == Some(domain.as_str()) {
+            problems.push(format!("Aplicação {} colide com o prefixo da API de administração", domain));
+        }
+        if !domains.insert(domain.clone()) {
+            problems.push(format!("Aplicação {} declarada mais de uma vez", domain));
+        }
+
+        url(problems, &format!("url da aplicação {}", domain), application.endpoint());
+        if let Some(guardian_url) = application.guardian_url() {
+            url(problems, &format!("guardian_url da aplicação {}", domain), guardian_url);
+        }
+
+        for route in application.routes() {
+            self::route(problems, &domain, route);
+        }
+        for limit in application.rate_limit().into_iter().chain(application.route_rate_limits().values()) {
+            if let Err(error) = limit.check() {
+                problems.push(format!("{} na aplicação {}", error, domain));
+            }
+        }
+        if let Some(error) = application.concurrency().and_then(|concurrency| concurrency.check().err()) {
+            problems.push(format!("{} na aplicação {}", error, domain));
+        }
+        let subject_limits = application.rate_limit().map(|limit| (application.guard(), limit))
+            .into_iter()
+            .chain(application.route_rate_limits().iter().map(|(route, limit)| (application.route_guards().get(route).unwrap_or(application.guard()), limit)));
+        for (strategy, _) in subject_limits.filter(|(_, limit)| limit.is_per_subject()) {
+            if !produces_subject(config, guardian, strategy) {
+                problems.push(format!("Limite por subject na aplicação {} exige uma guarda que devolva o sub, como jwt ou guardian com introspecção", domain));
+            }
+        }
+        for error in application.rules().iter().filter_map(|rule| rule.check().err()) {
+            problems.push(format!("{} na aplicação {}", error, domain));
+        }
+        if let Some(error) = application.cors().and_then(|cors| cors.check().err()) {
+            problems.push(format!("{} na aplicação {}", error, domain));
+        }
+        if let Some(path) = application.health_path().filter(|path| !path.starts_with('/')) {
+            problems.push(format!("health_path invalido '{}' na aplicação {}, use um caminho absoluto como /status", path, domain));
+        }
+
+        let names = guard_names(application);
+        for name in names.iter().filter(|name| !is_known_guard(config, name)) {
+            problems.push(format!("Guarda {} usada pela aplicação {} não foi configurada", name, domain));
+        }
+        for listener in application.listeners().iter().filter(|listener| !listener_names.contains(listener.as_str())) {
+            problems.push(format!("Listener {} usado pela aplicação {} não foi configurado", listener, domain));
+        }
+        if names.contains(&GUARDIAN) && application.guardian_url().is_none() {
+            guardian_users.push(domain);
+        }
+    }
+
+    if guardian.url.is_empty() && !guardian_users.is_empty() {
+        problems.push(format!("GUARDIAN_URL não configurada, exigida pelas aplicações {}", guardian_users.join(", ")));
+    } else if !guardian.url.is_empty() {
+        url(problems, "url do Guardião", &guardian.url);
+    }
+
+    for (name, definition) in &config.guards {
+        if name == GUARDIAN || name == NONE {
+            problems.push(format!("Guarda {} é embutida e não pode ser redefinida em guards", name));
+        }
+        match definition {
+            GuardDefinition::Guardian(definition) => url(problems, &format!("url da guarda {}", name), &definition.url),
+            GuardDefinition::Hmac(definition) => {
+                if let Err(error) = definition.check() {
+                    problems.push(format!("{} na guarda {}", error, name));
+                }
+            },
+            _ => (),
+        }
+    }
+}
+
+/// Devolve o primeiro segmento do prefixo quando a API de administração divide os listeners com o gateway.
+fn admin(config: &Config, problems: &mut Problems) -> Option<String> {
+    let admin = config.admin.as_ref()?;
+    if admin.tokens.is_empty() {
+        problems.push("API de administração sem tokens configurados".to_string());
+    }
+    if admin.address.is_some() {
+        if admin.tls == Some(true) && config.tls.is_none() {
+            problems.push("API de administração usa TLS, mas a seção tls não foi configurada".to_string());
+        }
+        return None;
+    }
+
+    let segment = admin.prefix.strip_prefix('/').and_then(|prefix| prefix.split('/').next()).unwrap_or_default();
+    if segment.is_empty() || admin.prefix.ends_with('/') {
+        problems.push(format!("Prefixo da API de administração invalido '{}', use algo como /_admin", admin.prefix));
+    }
+    Some(segment.to_lowercase())
+}
+
+/// Devolve os nomes dos listeners, que as aplicações podem citar.
+fn listeners<'a>(config: &'a Config, problems: &mut Problems) -> HashSet<&'a str> {
+    let mut names = HashSet::new();
+    let mut systemd = HashSet::new();
+    for listener in &config.listeners {
+        problems.check(listener.check());
+        if listener.tls == Some(true) && config.tls.is_none() {
+            problems.push(format!("Listener {} usa TLS, mas a seção tls não foi configurada", listener.describe()));
+        }
+        if let Some(name) = &listener.name {
+            if !names.insert(name.as_str()) {
+                problems.push(format!("Listener {} declarado mais de uma vez", name));
+            }
+        }
+        if let Some(index) = listener.systemd {
+            if !systemd.insert(index) {
+                problems.push(format!("Socket {} do systemd usado por mais de um listener", index));
+            }
+        }
+    }
+    if config.listeners.is_empty() {
+        problems.push("Nenhum listener configurado".to_string());
+    }
+    names
+}
+
+/// Intervalo ou tempo limite zerados travariam as verificações dos upstreams.
+fn health(config: &Config, problems: &mut Problems) {
+    if config.health.interval_ms == 0 {
+        problems.push("Intervalo das verificações de health precisa ser maior que zero".to_string());
+    }
+    if config.health.timeout_ms == 0 {
+        problems.push("Tempo limite das verificações de health precisa ser maior que zero".to_string());
+    }
+}
+
+fn guard_names(application: &Application) -> Vec<&str> {
+    let mut names = application.guard().names();
+    names.extend(application.route_guards().values().flat_map(|strategy| strategy.names()));
+    names
+}
+
+/// Só o JWT e o Guardião em modo de introspecção devolvem claims; no `any` qualquer uma pode liberar,
+/// então todas precisam devolver, e no `all` basta uma.
+fn produces_subject(config: &Config, guardian: &GuardianDefinition, strategy: &GuardStrategy) -> bool {
+    match strategy {
+        GuardStrategy::Named(name) if name == GUARDIAN => guardian.introspection.is_some(),
+        GuardStrategy::Named(name) => match config.guards.get(name) {
+            Some(GuardDefinition::Guardian(definition)) => definition.introspection.is_some(),
+            Some(GuardDefinition::Jwt(_)) => true,
+            _ => false,
+        },
+        GuardStrategy::Any { any } => any.iter().all(|strategy| produces_subject(config, guardian, strategy)),
+        GuardStrategy::All { all } => all.iter().any(|strategy| produces_subject(config, guardian, strategy)),
+    }
+}
+
+fn is_known_guard(config: &Config, name: &str) -> bool {
+    name == GUARDIAN || name == NONE || config.guards.contains_key(name)
+}
+
+fn url(problems: &mut Problems, subject: &str, value: &str) {
+    match Url::parse(value) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.has_host() => (),
+        Ok(_) => problems.push(format!("{} precisa ser http ou https com host: '{}'", subject, value)),
+        Err(error) => problems.push(format!("{} invalida '{}': {}", subject, value, error)),
+    }
+}
+
+/// Rotas são comparadas exatamente com o caminho da requisição, sem curingas.
+fn route(problems: &mut Problems, domain: &str, route: &Path) {
+    let valid = route.to_str()
+        .map(|route| route.starts_with('/') && !route.contains("//") && !route.contains(['*', '?', '#', ' ']))
+        .unwrap_or(false);
+    if !valid {
+        problems.push(format!("Rota invalida {:?} na aplicação {}, use um caminho absoluto como /status", route, domain));
+    }
+}